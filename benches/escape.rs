@@ -0,0 +1,115 @@
+//!
+//! Compares `tools::EscapeGuard` against the previous implementation,
+//! which wrote one char at a time.
+//!
+//! Run with `cargo bench`.
+//!
+
+use std::fmt::{self, Write};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use tagu::attr::PathCommand;
+use tagu::build;
+
+///
+/// The escaper as it was before it wrote unescaped runs in bulk.
+///
+struct OldEscapeGuard<T> {
+    writer: T,
+}
+
+impl<T: fmt::Write> fmt::Write for OldEscapeGuard<T> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            let r = match c {
+                '\"' => Some("&quot;"),
+                '\'' => Some("&apos;"),
+                '<' => Some("&lt;"),
+                '>' => Some("&gt;"),
+                '&' => Some("&amp;"),
+                _ => None,
+            };
+
+            if let Some(r) = r {
+                self.writer.write_str(r)?;
+            } else {
+                self.writer.write_char(c)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+///
+/// Run the closure repeatedly for about a second and return the time per run.
+///
+fn time(mut func: impl FnMut()) -> Duration {
+    func();
+    let start = Instant::now();
+    let mut runs = 0;
+    while start.elapsed() < Duration::from_secs(1) {
+        func();
+        runs += 1;
+    }
+    start.elapsed() / runs
+}
+
+fn compare(name: &str, input: &str) {
+    let mut buf = String::with_capacity(input.len() * 2);
+    let old = time(|| {
+        buf.clear();
+        let mut out = OldEscapeGuard { writer: &mut buf };
+        out.write_str(black_box(input)).unwrap();
+        black_box(&buf);
+    });
+    let new = time(|| {
+        buf.clear();
+        let mut out = tagu::tools::escape_guard(&mut buf);
+        out.write_str(black_box(input)).unwrap();
+        black_box(&buf);
+    });
+    println!(
+        "{:<24} old {:>12?}  new {:>12?}  {:.1}x",
+        name,
+        old,
+        new,
+        old.as_secs_f64() / new.as_secs_f64()
+    );
+}
+
+fn main() {
+    let n = 200_000;
+
+    let mut points = String::new();
+    for i in 0..n {
+        write!(points, "{},{} ", i, i * 2).unwrap();
+    }
+    compare("points value", &points);
+
+    let mut path = String::new();
+    for i in 0..n {
+        write!(path, " L {} {}", i as f64 * 0.5, i as f64 * 0.25).unwrap();
+    }
+    compare("path value", &path);
+
+    let text = "a < b && c > \"d\" ".repeat(n / 4);
+    compare("text with escapes", &text);
+
+    let render = time(|| {
+        let elem = build::single("polyline").with(build::points((0..n).map(|i| (i, i * 2))));
+        let mut s = String::new();
+        tagu::render(elem, &mut s).unwrap();
+        black_box(s);
+    });
+    println!("{:<24} {:>12?}", "render points", render);
+
+    let render = time(|| {
+        let elem = build::single("path").with(build::path(
+            (0..n).map(|i| PathCommand::L(i as f64 * 0.5, i as f64 * 0.25)),
+        ));
+        let mut s = String::new();
+        tagu::render(elem, &mut s).unwrap();
+        black_box(s);
+    });
+    println!("{:<24} {:>12?}", "render path", render);
+}
@@ -1,11 +1,8 @@
 use tagu::build;
 use tagu::prelude::*;
 
-fn main() -> std::fmt::Result {
-    let html = build::single("DOCTYPE html")
-        .with_start("!")
-        .with_ending("")
-        .chain(build::elem("html"));
+fn main() -> Result<(), tagu::Error> {
+    let html = build::doctype("html").chain(build::elem("html"));
 
     let style = build::elem("style").append(build::raw(
         "table, th, td {
@@ -1,14 +1,13 @@
 use tagu::build;
+use tagu::ns;
 use tagu::prelude::*;
 
-fn main() -> std::fmt::Result {
+fn main() -> Result<(), tagu::Error> {
     let width = 500.0;
     let height = 400.0;
 
-    let svg = build::elem("svg").with(attrs!(
-        ("xmlns", "http://www.w3.org/2000/svg"),
-        ("viewBox", format_move!("0 0 {} {}", width, height))
-    ));
+    let svg =
+        build::elem_ns(ns::SVG, "svg").with(("viewBox", format_move!("0 0 {} {}", width, height)));
 
     let polygon = build::single("polygon").with(attrs!(
         ("stroke", "black"),
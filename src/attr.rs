@@ -3,6 +3,8 @@
 //!
 
 use super::*;
+use crate::context::Context;
+use crate::sink::Sink;
 use fmt::Write;
 
 ///
@@ -64,30 +66,101 @@ impl<A: Attr, B: Attr> Attr for AttrChain<A, B> {
 ///
 /// The attr writer passed to the closure attr
 ///
-pub struct AttrWrite<'a>(WriteWrap<'a>);
+pub struct AttrWrite<'a>(&'a mut dyn Sink, &'a mut Context);
 impl<'a> AttrWrite<'a> {
-    pub(super) fn new(wrap: WriteWrap<'a>) -> Self {
-        AttrWrite(wrap)
+    pub(super) fn new(sink: &'a mut dyn Sink, ctx: &'a mut Context) -> Self {
+        AttrWrite(sink, ctx)
     }
     pub fn render<E: Attr>(&mut self, attr: E) -> fmt::Result {
         attr.render(self)
     }
-    pub fn writer(&mut self) -> tools::EscapeGuard<WriteWrap<'_>> {
-        tools::escape_guard(self.0.borrow_mut())
+
+    ///
+    /// Write an attribute whose value is formatted by a closure.
+    /// The value is escaped the same way as any other attribute value.
+    ///
+    pub fn write_attr(
+        &mut self,
+        name: impl fmt::Display,
+        value: impl FnOnce(&mut dyn fmt::Write) -> fmt::Result,
+    ) -> fmt::Result {
+        self.attribute(name, |v| value(v))
+    }
+
+    ///
+    /// Write attributes as markup, such as ` disabled` or ` width=100`. What is written
+    /// is split at whitespace into flags and `name=value` pairs, checked like any other.
+    ///
+    #[deprecated(note = "use AttrWrite::write_attr")]
+    pub fn writer(&mut self) -> impl fmt::Write + '_ {
+        &mut self.1.markup
     }
 
-    fn writer_escapable(&mut self) -> WriteWrap<'_> {
-        self.0.borrow_mut()
+    ///
+    /// Format a value with the given closure and emit it as an attribute.
+    ///
+    fn attribute(
+        &mut self,
+        name: impl fmt::Display,
+        value: impl FnOnce(&mut String) -> fmt::Result,
+    ) -> fmt::Result {
+        let ctx = &mut *self.1;
+        ctx.flush_markup(self.0).map_err(|e| ctx.fail(e))?;
+        ctx.name.clear();
+        write!(ctx.name, "{}", name)?;
+        ctx.value.clear();
+        value(&mut ctx.value)?;
+        ctx.attribute(self.0, false).map_err(|e| ctx.fail(e))
+    }
+
+    ///
+    /// Declare the namespace with an `xmlns` attribute, unless it is already in scope.
+    ///
+    pub(crate) fn declare(&mut self, ns: &crate::ns::Namespace) -> fmt::Result {
+        if self.1.namespace(ns.prefix()) == Some(ns.uri()) {
+            return Ok(());
+        }
+        let name = tools::disp_const(|f| match ns.prefix() {
+            "" => f.write_str("xmlns"),
+            prefix => write!(f, "xmlns:{}", prefix),
+        });
+        self.attribute(name, |v| v.write_str(ns.uri()))
+    }
+
+    fn flag(&mut self, name: impl fmt::Display) -> fmt::Result {
+        let ctx = &mut *self.1;
+        ctx.flush_markup(self.0).map_err(|e| ctx.fail(e))?;
+        ctx.name.clear();
+        write!(ctx.name, "{}", name)?;
+        ctx.value.clear();
+        ctx.attribute(self.0, true).map_err(|e| ctx.fail(e))
+    }
+}
+
+///
+/// A boolean attribute, such as `disabled`
+///
+#[derive(Copy, Clone)]
+#[must_use]
+pub struct Flag<D> {
+    name: D,
+}
+
+impl<D: fmt::Display> Flag<D> {
+    pub fn new(name: D) -> Self {
+        Flag { name }
+    }
+}
+impl<D: fmt::Display> Attr for Flag<D> {
+    fn render(self, w: &mut AttrWrite) -> std::fmt::Result {
+        w.flag(self.name)
     }
 }
 
 impl<A: fmt::Display, B: fmt::Display> Attr for (A, B) {
     fn render(self, w: &mut AttrWrite) -> std::fmt::Result {
         let (first, second) = self;
-        write!(w.writer(), " {}", first)?;
-        w.writer_escapable().write_str("=\"")?;
-        write!(w.writer(), "{}", second)?;
-        w.writer_escapable().write_str("\"")
+        w.attribute(first, |v| write!(v, "{}", second))
     }
 }
 
@@ -124,12 +197,12 @@ pub struct Path<I> {
 
 impl<I: IntoIterator<Item = PathCommand<D>>, D: fmt::Display> Attr for Path<I> {
     fn render(self, w: &mut AttrWrite) -> std::fmt::Result {
-        w.writer_escapable().write_str(" d=\"")?;
-
-        for command in self.iter {
-            command.write(w.writer())?;
-        }
-        w.writer_escapable().write_str("\"")
+        w.attribute("d", |v| {
+            for command in self.iter {
+                command.write(&mut *v)?;
+            }
+            Ok(())
+        })
     }
 }
 impl<I: IntoIterator<Item = PathCommand<D>>, D: fmt::Display> Path<I> {
@@ -141,24 +214,24 @@ impl<I: IntoIterator<Item = PathCommand<D>>, D: fmt::Display> Path<I> {
 ///
 /// path closure building blocks
 ///
-pub struct PathSinkBuilder<'a, 'b> {
-    writer: &'a mut AttrWrite<'b>,
+pub struct PathSinkBuilder<'a> {
+    writer: &'a mut String,
 }
 
 ///
 /// path closure building blocks
 ///
-pub struct PathSink<'a, 'b, T> {
-    writer: &'a mut AttrWrite<'b>,
+pub struct PathSink<'a, T> {
+    writer: &'a mut String,
     _p: std::marker::PhantomData<T>,
 }
-impl<T: fmt::Display> PathSink<'_, '_, T> {
+impl<T: fmt::Display> PathSink<'_, T> {
     pub fn put(&mut self, command: PathCommand<T>) -> fmt::Result {
-        command.write(self.writer.writer())
+        command.write(&mut *self.writer)
     }
 }
-impl<'a, 'b> PathSinkBuilder<'a, 'b> {
-    pub fn start<T>(self) -> PathSink<'a, 'b, T> {
+impl<'a> PathSinkBuilder<'a> {
+    pub fn start<T>(self) -> PathSink<'a, T> {
         PathSink {
             writer: self.writer,
             _p: std::marker::PhantomData,
@@ -194,9 +267,7 @@ impl<F: FnOnce(PathSinkBuilder) -> fmt::Result> PathClosure<F> {
 }
 impl<F: FnOnce(PathSinkBuilder) -> fmt::Result> Attr for PathClosure<F> {
     fn render(self, w: &mut AttrWrite) -> fmt::Result {
-        w.writer_escapable().write_str(" d=\"")?;
-        (self.func)(PathSinkBuilder { writer: w })?;
-        w.writer_escapable().write_str("\"")
+        w.attribute("d", |v| (self.func)(PathSinkBuilder { writer: v }))
     }
 }
 
@@ -216,11 +287,12 @@ impl<I: IntoIterator<Item = (D, D)>, D: fmt::Display> Points<I> {
 }
 impl<I: IntoIterator<Item = (D, D)>, D: fmt::Display> Attr for Points<I> {
     fn render(self, w: &mut AttrWrite) -> std::fmt::Result {
-        w.writer_escapable().write_str(" points=\"")?;
-        for (x, y) in self.iter {
-            write!(w.writer(), "{},{} ", x, y)?;
-        }
-        w.writer_escapable().write_str("\"")
+        w.attribute("points", |v| {
+            for (x, y) in self.iter {
+                write!(v, "{},{} ", x, y)?;
+            }
+            Ok(())
+        })
     }
 }
 
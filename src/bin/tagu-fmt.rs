@@ -0,0 +1,142 @@
+//!
+//! Reformat XML / SVG / HTML files the way tagu lays out markup.
+//!
+//! ```text
+//! tagu-fmt [OPTIONS] [FILE]...
+//! ```
+//!
+
+use std::io::{self, Read, Write};
+use std::process::ExitCode;
+use tagu::dom::Document;
+use tagu::parse::ParseOptions;
+use tagu::render::{Mode, RenderOptions};
+
+const USAGE: &str = "\
+Usage: tagu-fmt [OPTIONS] [FILE]...
+
+Reads each FILE, or stdin if there is none or FILE is -, and writes it
+back out formatted the way tagu renders markup.
+
+Options:
+  --indent N   indent with N spaces instead of a tab
+  --compact    write everything on one line
+  --trim       trim whitespace around text
+  --html       parse as HTML (default for .html and .htm files)
+  --xml        parse as XML (default otherwise)
+  -w, --write  write the result back to the file instead of stdout
+  --check      write nothing, fail if a file is not formatted
+  -h, --help   print this message
+";
+
+#[derive(Default)]
+struct Args {
+    indent: Option<usize>,
+    compact: bool,
+    trim: bool,
+    html: Option<bool>,
+    write: bool,
+    check: bool,
+    files: Vec<String>,
+}
+
+///
+/// The parsed arguments, or `None` if the usage was asked for.
+///
+fn parse_args() -> Result<Option<Args>, String> {
+    let mut args = Args::default();
+    let mut it = std::env::args().skip(1);
+    while let Some(a) = it.next() {
+        match a.as_str() {
+            "--indent" => {
+                let n = it.next().ok_or("--indent needs a value")?;
+                let n = n.parse().map_err(|_| format!("invalid indent {:?}", n))?;
+                args.indent = Some(n);
+            }
+            "--compact" => args.compact = true,
+            "--trim" => args.trim = true,
+            "--html" => args.html = Some(true),
+            "--xml" => args.html = Some(false),
+            "-w" | "--write" => args.write = true,
+            "--check" => args.check = true,
+            "-h" | "--help" => return Ok(None),
+            _ if a.starts_with('-') && a != "-" => return Err(format!("unknown option {}", a)),
+            _ => args.files.push(a),
+        }
+    }
+    if args.files.is_empty() {
+        args.files.push("-".to_string());
+    }
+    if args.write && args.files.iter().any(|f| f == "-") {
+        return Err("--write needs files to write to".to_string());
+    }
+    Ok(Some(args))
+}
+
+fn format(args: &Args, file: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let src = if file == "-" {
+        let mut s = String::new();
+        io::stdin().read_to_string(&mut s)?;
+        s
+    } else {
+        std::fs::read_to_string(file)?
+    };
+
+    let html = args
+        .html
+        .unwrap_or_else(|| file.ends_with(".html") || file.ends_with(".htm"));
+    let parser = if html {
+        ParseOptions::html()
+    } else {
+        ParseOptions::xml()
+    };
+    let doc: Document = parser.trim_text(args.trim).parse(&src)?;
+
+    let mut options = RenderOptions::new()
+        .inline(args.compact)
+        .trailing_newline(true);
+    if let Some(n) = args.indent {
+        options = options.indent_width(n);
+    }
+    options = options.mode(if html { Mode::Html } else { Mode::Xml });
+    let mut renderer = options.build();
+
+    let mut out = String::new();
+    renderer.render(&doc, &mut out)?;
+
+    if args.check {
+        if out != src {
+            return Err("not formatted".into());
+        }
+    } else if args.write {
+        if out != src {
+            tagu::tools::write_file_atomic(file, |f| f.write_all(out.as_bytes()))?;
+        }
+    } else {
+        print!("{}", out);
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            print!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("tagu-fmt: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let mut code = ExitCode::SUCCESS;
+    for file in args.files.iter() {
+        if let Err(e) = format(&args, file) {
+            eprintln!("tagu-fmt: {}: {}", file, e);
+            code = ExitCode::FAILURE;
+        }
+    }
+    code
+}
@@ -3,6 +3,7 @@
 //!
 
 use super::*;
+use crate::ns::{Declare, Namespace, NsAttr, QName};
 
 ///
 /// Create an escapable element
@@ -22,6 +23,49 @@ pub fn raw<D: fmt::Display>(data: D) -> Raw<D> {
     Raw::new(data)
 }
 
+///
+/// Create a comment. Its text is not escaped, so rendering fails if it would end the comment
+/// early, and in [`Mode::Xml`](crate::render::Mode::Xml) and
+/// [`Mode::Xhtml`](crate::render::Mode::Xhtml) if it has a `--` or a trailing `-`.
+///
+pub fn comment<D: fmt::Display>(text: D) -> Comment<D> {
+    Comment::new(text)
+}
+
+///
+/// Create a CDATA section. A `]]>` in the text is split across two sections.
+/// In [`Mode::Html`](crate::render::Mode::Html) the text is written as escaped text instead.
+///
+pub fn cdata<D: fmt::Display>(text: D) -> Cdata<D> {
+    Cdata::new(text)
+}
+
+///
+/// Create a document type declaration, such as `<!DOCTYPE html>`.
+///
+pub fn doctype<D: fmt::Display>(doctype: D) -> Doctype<D> {
+    Doctype::new(doctype)
+}
+
+///
+/// Create the XML declaration, `<?xml version="1.0"?>`,
+/// with an optional encoding and standalone declaration.
+///
+pub fn xml_declaration() -> XmlDeclaration<&'static str> {
+    XmlDeclaration::new()
+}
+
+///
+/// Create a processing instruction. The target must be a valid name other than `xml`,
+/// and the data cannot contain `?>`.
+///
+pub fn processing_instruction<T: fmt::Display, D: fmt::Display>(
+    target: T,
+    data: D,
+) -> ProcessingInstruction<T, D> {
+    ProcessingInstruction::new(target, data)
+}
+
 ///
 /// Create an escapable element from a closure
 ///
@@ -72,14 +116,37 @@ pub fn from_closure2<F: FnOnce() -> E, E: Elem>(func: F) -> Closure2<F> {
 use crate::stack::*;
 pub fn from_stack<F>(func: F) -> Sess<F>
 where
-    F: FnOnce(ElemStack<Sentinel>) -> Result<ElemStack<Sentinel>, fmt::Error>,
+    F: FnOnce(ElemStack<Sentinel>) -> Result<ElemStack<Sentinel>, Error>,
+{
+    Sess::new(func)
+}
+
+///
+/// Same as [`from_stack`] but the closure returns its own error type,
+/// which [`Sess::render`] gives back as is.
+///
+pub fn try_from_stack<F, E>(func: F) -> Sess<F, E>
+where
+    F: FnOnce(ElemStack<Sentinel>) -> Result<ElemStack<Sentinel>, E>,
+    E: From<Error>,
 {
     Sess::new(func)
 }
 
 pub fn from_stack_escapable<F>(func: F) -> SessEscapable<F>
 where
-    F: FnOnce(ElemStackEscapable<Sentinel>) -> Result<ElemStackEscapable<Sentinel>, fmt::Error>,
+    F: FnOnce(ElemStackEscapable<Sentinel>) -> Result<ElemStackEscapable<Sentinel>, Error>,
+{
+    SessEscapable::new(func)
+}
+
+///
+/// Same as [`from_stack_escapable`] but the closure returns its own error type.
+///
+pub fn try_from_stack_escapable<F, E>(func: F) -> SessEscapable<F, E>
+where
+    F: FnOnce(ElemStackEscapable<Sentinel>) -> Result<ElemStackEscapable<Sentinel>, E>,
+    E: From<Error>,
 {
     SessEscapable::new(func)
 }
@@ -124,6 +191,44 @@ pub fn elem<D: fmt::Display>(tag: D) -> Element<D, ()> {
     Element::new(tag)
 }
 
+///
+/// Create an element in a namespace. The namespace is declared on it
+/// unless an ancestor already did.
+///
+pub fn elem_ns<D: fmt::Display>(
+    ns: Namespace,
+    tag: D,
+) -> Element<QName<D>, AttrChain<(), Declare>> {
+    Element::new(ns.name(tag)).with(Declare::new(ns))
+}
+
+///
+/// Create an element in a namespace that has no closing tag.
+/// See [`elem_ns`].
+///
+pub fn single_ns<D: fmt::Display>(
+    ns: Namespace,
+    tag: D,
+) -> Single<QName<D>, AttrChain<(), Declare>, &'static str, &'static str> {
+    Single::new(ns.name(tag)).with(Declare::new(ns))
+}
+
+///
+/// Create an attribute in a namespace, such as `xlink:href`. The namespace is
+/// declared on the element unless an ancestor already did. See [`elem_ns`].
+///
+pub fn attr_ns<D: fmt::Display, V: fmt::Display>(ns: Namespace, name: D, value: V) -> NsAttr<D, V> {
+    NsAttr::new(ns, name, value)
+}
+
+///
+/// Declare a namespace on an element without using it there,
+/// so that its descendants do not each declare it.
+///
+pub fn xmlns(ns: Namespace) -> Declare {
+    Declare::new(ns)
+}
+
 ///
 /// Box an element
 ///
@@ -157,6 +262,14 @@ pub fn attr_from_closure<F: FnOnce(&mut AttrWrite) -> fmt::Result>(func: F) -> A
     AttrClosure::new(func)
 }
 
+///
+/// Create a boolean attribute. In [`Mode::Html`](crate::render::Mode::Html)
+/// only the name is written, otherwise the name is repeated as the value.
+///
+pub fn flag<D: fmt::Display>(name: D) -> Flag<D> {
+    Flag::new(name)
+}
+
 ///
 /// Create a path attribute
 ///
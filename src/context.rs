@@ -0,0 +1,418 @@
+//!
+//! State shared by everything rendering into the same output
+//!
+
+use crate::error::Error;
+use crate::render::{DuplicateAttrs, Mode, RenderOptions};
+use crate::sink::{self, Sink};
+use std::fmt::{self, Write as _};
+
+///
+/// State shared by everything rendering into the same output.
+///
+/// Tracks the open tags and the cause of a failure.
+/// Elements only return `fmt::Error`, so the real cause is stashed here
+/// and picked back up at the boundary where a [`Error`] is returned.
+///
+/// Also holds scratch buffers for formatting names and values before they are handed to a [`Sink`].
+///
+#[derive(Default)]
+pub(crate) struct Context {
+    names: String,
+    starts: Vec<usize>,
+    error: Option<Error>,
+    pub name: String,
+    pub value: String,
+    duplicate_attrs: DuplicateAttrs,
+    check_namespaces: bool,
+    /// The namespace prefixes in scope, with the depth of the tag that declared them.
+    bindings: Vec<(String, String, usize)>,
+    pub html: bool,
+    /// The attributes of the current start tag. Only the names are kept
+    /// for [`DuplicateAttrs::Allow`], otherwise they are held back until the tag ends.
+    /// The entries past `attr_count` are spare, to reuse their strings.
+    attrs: Vec<PendingAttr>,
+    attr_count: usize,
+    /// The start tag is held back with the attributes.
+    pending_start: bool,
+    /// The current tag is a declaration rather than an element.
+    declaration: bool,
+    /// Check the values of URL attributes.
+    urls: bool,
+    /// The depth of the `script` or `style` element whose content is being written.
+    raw_text: Option<usize>,
+    /// The end of the raw text so far, to catch an end tag split across writes.
+    raw_text_tail: String,
+    /// Attributes written as markup through the deprecated `AttrWrite::writer`.
+    pub markup: String,
+}
+
+#[derive(Default)]
+struct PendingAttr {
+    name: String,
+    value: String,
+    flag: bool,
+}
+
+impl Context {
+    pub fn new(options: &RenderOptions) -> Self {
+        Context {
+            duplicate_attrs: options.duplicate_attrs,
+            html: options.text.mode == Some(Mode::Html),
+            urls: matches!(options.text.mode, Some(Mode::Html | Mode::Xhtml)),
+            // HTML does not know about prefixes, and output without a mode is left as written.
+            check_namespaces: matches!(options.text.mode, Some(Mode::Xml | Mode::Xhtml)),
+            bindings: options
+                .namespaces
+                .iter()
+                .map(|ns| (ns.prefix().to_string(), ns.uri().to_string(), 0))
+                .collect(),
+            ..Self::default()
+        }
+    }
+
+    pub fn push_tag(&mut self, tag: impl fmt::Display) -> fmt::Result {
+        self.attr_count = 0;
+        self.pending_start = false;
+        self.declaration = false;
+        self.markup.clear();
+        self.starts.push(self.names.len());
+        write!(self.names, "{}", tag)
+    }
+
+    ///
+    /// Like `push_tag`, for markup such as `<!DOCTYPE html>` whose name and
+    /// attributes are not element names and so are not checked for prefixes.
+    ///
+    pub fn push_declaration(&mut self, tag: impl fmt::Display) -> fmt::Result {
+        self.push_tag(tag)?;
+        self.declaration = true;
+        Ok(())
+    }
+
+    ///
+    /// Start the current tag. Unless duplicate attributes are allowed, the start tag
+    /// is held back with the attributes, so a tag that fails leaves no partial output.
+    ///
+    pub fn start_tag(&mut self, sink: &mut dyn Sink) -> Result<(), Error> {
+        if self.duplicate_attrs == DuplicateAttrs::Allow {
+            sink.start_tag(self.tag())
+        } else {
+            self.pending_start = true;
+            Ok(())
+        }
+    }
+
+    ///
+    /// Emit the attribute in `name` and `value`, or a flag if `flag` is set,
+    /// applying the policy for duplicates.
+    ///
+    pub fn attribute(&mut self, sink: &mut dyn Sink, flag: bool) -> Result<(), Error> {
+        if self.urls && !flag && sink::is_url_attr(&self.name) {
+            self.value = sink::url_value(&self.name, &self.value)?;
+        }
+        // HTML attribute names are case-insensitive.
+        let html = self.html;
+        let is = move |a: &str, b: &str| {
+            if html {
+                a.eq_ignore_ascii_case(b)
+            } else {
+                a == b
+            }
+        };
+        let name = self.name.as_str();
+        let attrs = &mut self.attrs[..self.attr_count];
+        let found = attrs.iter_mut().find(|a| is(&a.name, name));
+        match (self.duplicate_attrs, found) {
+            (DuplicateAttrs::Allow, _) => {
+                if flag {
+                    sink.flag(name)?;
+                } else {
+                    sink.attribute(name, &self.value)?;
+                }
+            }
+            (DuplicateAttrs::Error, Some(_)) => {
+                return Err(Error::validation(format!("duplicate attribute {:?}", name)))
+            }
+            (DuplicateAttrs::Merge, Some(a)) if !a.flag && !flag && is(&a.name, "class") => {
+                for class in self.value.split_whitespace() {
+                    if !a.value.split_whitespace().any(|c| c == class) {
+                        if !a.value.is_empty() {
+                            a.value.push(' ');
+                        }
+                        a.value.push_str(class);
+                    }
+                }
+                return Ok(());
+            }
+            (DuplicateAttrs::Merge, Some(a)) if !a.flag && !flag && is(&a.name, "style") => {
+                let len = a.value.trim_end_matches([';', ' ']).len();
+                a.value.truncate(len);
+                if !a.value.is_empty() {
+                    a.value.push_str("; ");
+                }
+                a.value.push_str(self.value.trim());
+                return Ok(());
+            }
+            (_, Some(a)) => {
+                a.flag = flag;
+                a.value.clear();
+                a.value.push_str(&self.value);
+                self.bind();
+                return Ok(());
+            }
+            (_, None) => {}
+        }
+
+        if self.attr_count == self.attrs.len() {
+            self.attrs.push(PendingAttr::default());
+        }
+        self.bind();
+
+        let a = &mut self.attrs[self.attr_count];
+        self.attr_count += 1;
+        a.name.clear();
+        a.name.push_str(&self.name);
+        a.flag = flag;
+        a.value.clear();
+        if self.duplicate_attrs != DuplicateAttrs::Allow {
+            a.value.push_str(&self.value);
+        }
+        Ok(())
+    }
+
+    ///
+    /// Emit the start tag and attributes held back for the current tag.
+    ///
+    pub fn flush_attrs(&mut self, sink: &mut dyn Sink) -> Result<(), Error> {
+        self.flush_markup(sink)?;
+        self.check_prefixes()?;
+        let count = std::mem::take(&mut self.attr_count);
+        if !std::mem::take(&mut self.pending_start) {
+            return Ok(());
+        }
+        sink.start_tag(self.tag())?;
+        for a in &self.attrs[..count] {
+            if a.flag {
+                sink.flag(&a.name)?;
+            } else {
+                sink.attribute(&a.name, &a.value)?;
+            }
+        }
+        Ok(())
+    }
+
+    ///
+    /// Turn the attribute markup written so far into attributes. It is split at whitespace
+    /// into flags and `name=value` pairs.
+    ///
+    pub fn flush_markup(&mut self, sink: &mut dyn Sink) -> Result<(), Error> {
+        if self.markup.is_empty() {
+            return Ok(());
+        }
+        let markup = std::mem::take(&mut self.markup);
+        for token in markup.split_whitespace() {
+            let (name, value) = token.split_once('=').unwrap_or((token, ""));
+            self.name.clear();
+            self.name.push_str(name);
+            self.value.clear();
+            self.value.push_str(value);
+            self.attribute(sink, !token.contains('='))?;
+        }
+        Ok(())
+    }
+
+    ///
+    /// Record the namespace bound by an `xmlns` attribute in `name` and `value`.
+    /// It replaces what the same attribute bound earlier on the tag.
+    ///
+    fn bind(&mut self) {
+        let prefix = match self.name.strip_prefix("xmlns") {
+            Some("") => "",
+            Some(rest) => match rest.strip_prefix(':') {
+                Some(prefix) => prefix,
+                None => return,
+            },
+            None => return,
+        };
+        let depth = self.starts.len();
+        let found = self
+            .bindings
+            .iter_mut()
+            .rev()
+            .take_while(|b| b.2 == depth)
+            .find(|b| b.0 == prefix);
+        match found {
+            Some(b) => {
+                b.1.clear();
+                b.1.push_str(&self.value);
+            }
+            None => self
+                .bindings
+                .push((prefix.to_string(), self.value.clone(), depth)),
+        }
+    }
+
+    pub fn pop_tag(&mut self) {
+        if let Some(start) = self.starts.pop() {
+            self.names.truncate(start);
+        }
+        let depth = self.starts.len();
+        if self.raw_text.is_some_and(|d| d > depth) {
+            self.raw_text = None;
+        }
+        while self.bindings.last().is_some_and(|b| b.2 > depth) {
+            self.bindings.pop();
+        }
+    }
+
+    ///
+    /// The content of the current element starts. The content of `script` and `style`
+    /// is raw text, which HTML does not decode.
+    ///
+    pub fn start_content(&mut self) {
+        if self.raw_text.is_none() && sink::is_html_raw_text(self.tag()) {
+            self.raw_text = Some(self.starts.len());
+            self.raw_text_tail.clear();
+        }
+    }
+
+    ///
+    /// If the content being written is raw text.
+    ///
+    pub fn is_raw_text(&self) -> bool {
+        self.raw_text.is_some()
+    }
+
+    ///
+    /// Fail if the content being written is raw text, which cannot contain `what`.
+    ///
+    pub fn check_not_raw_text(&self, what: &str) -> Result<(), Error> {
+        match self.raw_text {
+            Some(_) => Err(Error::validation(format!(
+                "{} cannot contain {}",
+                self.tag(),
+                what
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    ///
+    /// Check the next piece of raw text. Since it is not escaped,
+    /// the end tag of the element must not appear in it.
+    ///
+    pub fn check_raw_text(&mut self, text: &str) -> Result<(), Error> {
+        let name = self.tag().to_ascii_lowercase();
+        let tail = &mut self.raw_text_tail;
+        tail.push_str(text);
+        // An end tag right at the end of the text so far is only one if the next write says so.
+        let len = name.len() + 2;
+        if sink::find_raw_text_end(tail, &name).is_some_and(|i| i + len < tail.len()) {
+            return Err(Error::validation(format!(
+                "{} content cannot contain </{}",
+                self.tag(),
+                name
+            )));
+        }
+        let mut keep = tail.len().saturating_sub(len);
+        while !tail.is_char_boundary(keep) {
+            keep += 1;
+        }
+        tail.drain(..keep);
+        Ok(())
+    }
+
+    ///
+    /// How many tags are open.
+    ///
+    pub fn depth(&self) -> usize {
+        self.starts.len()
+    }
+
+    ///
+    /// Close the tags that a failed render left open, back to `depth`.
+    ///
+    pub fn unwind(&mut self, depth: usize) {
+        while self.starts.len() > depth {
+            self.pop_tag();
+        }
+    }
+
+    ///
+    /// The namespace bound to a prefix, the empty prefix being the default namespace.
+    ///
+    pub fn namespace(&self, prefix: &str) -> Option<&str> {
+        match prefix {
+            "xml" => Some("http://www.w3.org/XML/1998/namespace"),
+            "xmlns" => Some("http://www.w3.org/2000/xmlns/"),
+            _ => self
+                .bindings
+                .iter()
+                .rev()
+                .find(|b| b.0 == prefix)
+                .map(|b| b.1.as_str()),
+        }
+    }
+
+    ///
+    /// Check that the prefixes of the current tag and its attributes are declared.
+    ///
+    fn check_prefixes(&self) -> Result<(), Error> {
+        let tag = self.tag();
+        if !self.check_namespaces || self.declaration {
+            return Ok(());
+        }
+        let names = self.attrs[..self.attr_count]
+            .iter()
+            .map(|a| a.name.as_str());
+        for name in std::iter::once(tag).chain(names) {
+            if let Some((prefix, _)) = name.split_once(':') {
+                if self.namespace(prefix).is_none() {
+                    return Err(Error::validation(format!(
+                        "namespace prefix {:?} of {:?} is not declared",
+                        prefix, name
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The most recently pushed tag name.
+    pub fn tag(&self) -> &str {
+        self.starts.last().map(|&s| &self.names[s..]).unwrap_or("")
+    }
+
+    fn path(&self) -> Vec<String> {
+        let mut ends = self
+            .starts
+            .iter()
+            .skip(1)
+            .copied()
+            .chain([self.names.len()]);
+        self.starts
+            .iter()
+            .map(|&s| self.names[s..ends.next().unwrap()].to_string())
+            .collect()
+    }
+
+    ///
+    /// Record the cause of a failure, returning the `fmt::Error` to propagate.
+    ///
+    pub fn fail(&mut self, e: Error) -> fmt::Error {
+        let e = e.with_path(|| self.path());
+        self.error = Some(e);
+        fmt::Error
+    }
+
+    ///
+    /// Retrieve the cause of a failure that surfaced as a `fmt::Error`.
+    ///
+    pub fn take_error(&mut self, _: fmt::Error) -> Error {
+        match self.error.take() {
+            Some(e) => e,
+            None => Error::from(fmt::Error).with_path(|| self.path()),
+        }
+    }
+}
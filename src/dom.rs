@@ -0,0 +1,687 @@
+//!
+//! Owned tree of nodes that rendering can be captured into
+//!
+
+use super::*;
+use crate::sink::Sink;
+
+///
+/// A node of a captured tree.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Node {
+    Element(Element),
+    Text(String),
+    Comment(String),
+    Raw(String),
+}
+
+impl Node {
+    pub fn as_element(&self) -> Option<&Element> {
+        match self {
+            Node::Element(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Node::Text(t) => Some(t),
+            _ => None,
+        }
+    }
+}
+
+///
+/// An element of a captured tree, with its attributes in the order they were written.
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Element {
+    pub name: String,
+    pub attrs: Vec<(String, String)>,
+    pub children: Vec<Node>,
+    /// Whether the element was written as a self-closing tag instead of a start and end tag.
+    pub self_closing: bool,
+}
+
+impl Element {
+    pub fn new(name: impl Into<String>) -> Self {
+        Element {
+            name: name.into(),
+            attrs: vec![],
+            children: vec![],
+            self_closing: false,
+        }
+    }
+
+    ///
+    /// The value of the first attribute with the given name.
+    ///
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    ///
+    /// The children that are elements.
+    ///
+    pub fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(Node::as_element)
+    }
+
+    ///
+    /// All text inside this element concatenated.
+    ///
+    pub fn text(&self) -> String {
+        fn walk(e: &Element, out: &mut String) {
+            for c in e.children.iter() {
+                match c {
+                    Node::Element(e) => walk(e, out),
+                    Node::Text(t) => out.push_str(t),
+                    _ => {}
+                }
+            }
+        }
+        let mut s = String::new();
+        walk(self, &mut s);
+        s
+    }
+}
+
+///
+/// Sink that builds a [`Node`] tree.
+///
+/// Formatting whitespace is ignored and consecutive text is merged into one node.
+///
+#[derive(Default)]
+pub struct TreeSink {
+    open: Vec<Element>,
+    nodes: Vec<Node>,
+}
+
+impl TreeSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///
+    /// The nodes captured so far. Fails if an element was left open.
+    ///
+    pub fn into_nodes(self) -> Result<Vec<Node>, Error> {
+        match self.open.last() {
+            Some(e) => Err(Error::validation(format!(
+                "element {} is not closed",
+                e.name
+            ))),
+            None => Ok(self.nodes),
+        }
+    }
+
+    fn children(&mut self) -> &mut Vec<Node> {
+        match self.open.last_mut() {
+            Some(e) => &mut e.children,
+            None => &mut self.nodes,
+        }
+    }
+
+    fn close(&mut self, self_closing: bool) -> Result<(), Error> {
+        let mut e = self
+            .open
+            .pop()
+            .ok_or_else(|| Error::validation("end tag without a start tag"))?;
+        e.self_closing = self_closing;
+        self.children().push(Node::Element(e));
+        Ok(())
+    }
+}
+
+impl Sink for TreeSink {
+    fn start_tag(&mut self, name: &str) -> Result<(), Error> {
+        self.open.push(Element::new(name));
+        Ok(())
+    }
+    fn attribute(&mut self, name: &str, value: &str) -> Result<(), Error> {
+        let e = self
+            .open
+            .last_mut()
+            .ok_or_else(|| Error::validation("attribute outside of a start tag"))?;
+        e.attrs.push((name.to_string(), value.to_string()));
+        Ok(())
+    }
+    fn start_tag_end(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+    fn empty_tag_end(&mut self, _: &str) -> Result<(), Error> {
+        self.close(true)
+    }
+    fn end_tag(&mut self, name: &str) -> Result<(), Error> {
+        match self.open.last() {
+            Some(e) if e.name != name => Err(Error::validation(format!(
+                "end tag {} does not match start tag {}",
+                name, e.name
+            ))),
+            _ => self.close(false),
+        }
+    }
+    fn text(&mut self, text: &str) -> Result<(), Error> {
+        let children = self.children();
+        if let Some(Node::Text(t)) = children.last_mut() {
+            t.push_str(text);
+        } else {
+            children.push(Node::Text(text.to_string()));
+        }
+        Ok(())
+    }
+    fn raw(&mut self, markup: &str) -> Result<(), Error> {
+        // Each declaration is a node of its own, so it can be checked on its own.
+        self.children().push(Node::Raw(markup.to_string()));
+        Ok(())
+    }
+    fn comment(&mut self, text: &str) -> Result<(), Error> {
+        self.children().push(Node::Comment(text.to_string()));
+        Ok(())
+    }
+}
+
+///
+/// Render elements into an owned tree instead of text.
+///
+pub fn capture<E: Elem + Locked>(elem: E) -> Result<Vec<Node>, Error> {
+    let mut sink = TreeSink::new();
+    render_sink(elem, &mut sink)?;
+    sink.into_nodes()
+}
+
+///
+/// Handle to a node in a [`Document`].
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+enum Data {
+    Root,
+    Element {
+        name: String,
+        attrs: Vec<(String, String)>,
+        self_closing: bool,
+    },
+    Text(String),
+    Comment(String),
+    Raw(String),
+}
+
+struct Entry {
+    data: Data,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+}
+
+///
+/// Owned tree of nodes that can be changed after it is built and then rendered.
+/// Nodes that are removed are detached from the tree and can be inserted again.
+///
+pub struct Document {
+    entries: Vec<Entry>,
+}
+
+impl Default for Document {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Document {
+    pub fn new() -> Self {
+        Document {
+            entries: vec![Entry {
+                data: Data::Root,
+                parent: None,
+                children: vec![],
+            }],
+        }
+    }
+
+    ///
+    /// Render elements into a new document.
+    ///
+    pub fn capture<E: Elem + Locked>(elem: E) -> Result<Self, Error> {
+        let mut doc = Document::new();
+        let root = doc.root();
+        for node in capture(elem)? {
+            doc.insert_node(root, node);
+        }
+        Ok(doc)
+    }
+
+    fn insert_node(&mut self, parent: NodeId, node: Node) {
+        let id = match node {
+            Node::Element(e) => {
+                let id = self.create(Data::Element {
+                    name: e.name,
+                    attrs: e.attrs,
+                    self_closing: e.self_closing,
+                });
+                for c in e.children {
+                    self.insert_node(id, c);
+                }
+                id
+            }
+            Node::Text(t) => self.create(Data::Text(t)),
+            Node::Comment(t) => self.create(Data::Comment(t)),
+            Node::Raw(t) => self.create(Data::Raw(t)),
+        };
+        self.append_child(parent, id);
+    }
+
+    ///
+    /// The node that holds the top level nodes. It is not rendered itself.
+    ///
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    fn create(&mut self, data: Data) -> NodeId {
+        self.entries.push(Entry {
+            data,
+            parent: None,
+            children: vec![],
+        });
+        NodeId(self.entries.len() - 1)
+    }
+
+    ///
+    /// Create a detached element. It is written as a self-closing tag
+    /// while it has no children, see [`Document::set_self_closing`].
+    ///
+    pub fn create_element(&mut self, name: impl Into<String>) -> NodeId {
+        self.create(Data::Element {
+            name: name.into(),
+            attrs: vec![],
+            self_closing: true,
+        })
+    }
+
+    ///
+    /// Create a detached text node.
+    ///
+    pub fn create_text(&mut self, text: impl Into<String>) -> NodeId {
+        self.create(Data::Text(text.into()))
+    }
+
+    ///
+    /// Create a detached comment.
+    ///
+    pub fn create_comment(&mut self, text: impl Into<String>) -> NodeId {
+        self.create(Data::Comment(text.into()))
+    }
+
+    ///
+    /// Create a detached node of markup that is written as is.
+    ///
+    pub(crate) fn create_raw(&mut self, markup: impl Into<String>) -> NodeId {
+        self.create(Data::Raw(markup.into()))
+    }
+
+    ///
+    /// Create an element and append it to `parent`.
+    ///
+    pub fn append_element(&mut self, parent: NodeId, name: impl Into<String>) -> NodeId {
+        let id = self.create_element(name);
+        self.append_child(parent, id);
+        id
+    }
+
+    ///
+    /// Create a text node and append it to `parent`.
+    ///
+    pub fn append_text(&mut self, parent: NodeId, text: impl Into<String>) -> NodeId {
+        let id = self.create_text(text);
+        self.append_child(parent, id);
+        id
+    }
+
+    ///
+    /// Move `child` to the end of the children of `parent`.
+    ///
+    /// Panics if `child` is `parent` or one of its ancestors.
+    ///
+    pub fn append_child(&mut self, parent: NodeId, child: NodeId) {
+        let index = self.entries[parent.0].children.len();
+        self.insert_child(parent, index, child)
+    }
+
+    ///
+    /// Move `child` to position `index` in the children of `parent`.
+    ///
+    /// Panics if `child` is `parent` or one of its ancestors, or if `index` is out of bounds.
+    ///
+    pub fn insert_child(&mut self, parent: NodeId, index: usize, child: NodeId) {
+        // A node without children can only be its own ancestor, so new nodes skip the walk.
+        let leaf = self.entries[child.0].children.is_empty();
+        assert!(
+            child != parent && (leaf || !self.ancestors(parent).any(|a| a == child)),
+            "cannot insert a node into itself"
+        );
+        assert!(
+            !matches!(
+                self.entries[parent.0].data,
+                Data::Text(_) | Data::Comment(_) | Data::Raw(_)
+            ),
+            "only elements can have children"
+        );
+        self.remove(child);
+        self.entries[parent.0].children.insert(index, child);
+        self.entries[child.0].parent = Some(parent);
+    }
+
+    ///
+    /// Detach a node from its parent.
+    ///
+    pub fn remove(&mut self, id: NodeId) {
+        if let Some(parent) = self.entries[id.0].parent.take() {
+            self.entries[parent.0].children.retain(|&c| c != id);
+        }
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.entries[id.0].parent
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.entries[id.0].children
+    }
+
+    ///
+    /// The node itself followed by its parent, its parent's parent and so on.
+    ///
+    pub fn ancestors(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        std::iter::successors(Some(id), |&id| self.parent(id))
+    }
+
+    ///
+    /// The name of an element, or `None` for other nodes.
+    ///
+    pub fn name(&self, id: NodeId) -> Option<&str> {
+        match &self.entries[id.0].data {
+            Data::Element { name, .. } => Some(name),
+            _ => None,
+        }
+    }
+
+    ///
+    /// The attributes of an element, in order. Other nodes have none.
+    ///
+    pub fn attrs(&self, id: NodeId) -> &[(String, String)] {
+        match &self.entries[id.0].data {
+            Data::Element { attrs, .. } => attrs,
+            _ => &[],
+        }
+    }
+
+    pub fn attr(&self, id: NodeId, name: &str) -> Option<&str> {
+        self.attrs(id)
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    ///
+    /// All text inside a node concatenated.
+    ///
+    pub fn text(&self, id: NodeId) -> String {
+        fn walk(doc: &Document, id: NodeId, out: &mut String) {
+            match &doc.entries[id.0].data {
+                Data::Text(t) => out.push_str(t),
+                Data::Root | Data::Element { .. } => {
+                    for &c in doc.children(id) {
+                        walk(doc, c, out);
+                    }
+                }
+                _ => {}
+            }
+        }
+        let mut s = String::new();
+        walk(self, id, &mut s);
+        s
+    }
+
+    ///
+    /// Whether an element without children is written as a self-closing tag
+    /// or as a start and end tag. Other nodes are never self-closing.
+    ///
+    pub fn self_closing(&self, id: NodeId) -> bool {
+        matches!(
+            self.entries[id.0].data,
+            Data::Element {
+                self_closing: true,
+                ..
+            }
+        )
+    }
+
+    ///
+    /// Choose how an element without children is written.
+    ///
+    /// Panics if the node is not an element.
+    ///
+    pub fn set_self_closing(&mut self, id: NodeId, value: bool) {
+        match &mut self.entries[id.0].data {
+            Data::Element { self_closing, .. } => *self_closing = value,
+            _ => panic!("node is not an element"),
+        }
+    }
+
+    fn attrs_mut(&mut self, id: NodeId) -> &mut Vec<(String, String)> {
+        match &mut self.entries[id.0].data {
+            Data::Element { attrs, .. } => attrs,
+            _ => panic!("node is not an element"),
+        }
+    }
+
+    ///
+    /// Set an attribute, replacing the value if it already exists.
+    ///
+    /// Panics if the node is not an element.
+    ///
+    pub fn set_attr(&mut self, id: NodeId, name: impl Into<String>, value: impl Into<String>) {
+        let name = name.into();
+        let value = value.into();
+        let attrs = self.attrs_mut(id);
+        match attrs.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, v)) => *v = value,
+            None => attrs.push((name, value)),
+        }
+    }
+
+    ///
+    /// Remove an attribute and return its value.
+    ///
+    /// Panics if the node is not an element.
+    ///
+    pub fn remove_attr(&mut self, id: NodeId, name: &str) -> Option<String> {
+        let attrs = self.attrs_mut(id);
+        let pos = attrs.iter().position(|(n, _)| n == name)?;
+        Some(attrs.remove(pos).1)
+    }
+
+    ///
+    /// Add a class to the `class` attribute if it is not there already.
+    ///
+    /// Panics if the node is not an element.
+    ///
+    pub fn add_class(&mut self, id: NodeId, class: &str) {
+        let value = match self.attr(id, "class") {
+            Some(v) if v.split_whitespace().any(|c| c == class) => return,
+            Some(v) if !v.trim().is_empty() => format!("{} {}", v, class),
+            _ => class.to_string(),
+        };
+        self.set_attr(id, "class", value);
+    }
+
+    ///
+    /// An element that renders the node and everything below it.
+    ///
+    pub fn subtree(&self, id: NodeId) -> Subtree<'_> {
+        Subtree { doc: self, id }
+    }
+
+    ///
+    /// Render a node and everything below it. Elements that hold text are kept as they
+    /// are, others are laid out with their children on lines of their own.
+    ///
+    fn render_node(&self, id: NodeId, w: &mut ElemWrite) -> fmt::Result {
+        enum Step<B, I> {
+            // A node, and whether it is inside an element kept as it is.
+            Node(NodeId, bool),
+            Block(B),
+            Inline(I),
+        }
+
+        // A stack rather than recursion, so deep documents do not overflow.
+        let mut stack = vec![Step::Node(id, false)];
+        while let Some(step) = stack.pop() {
+            let (id, inline) = match step {
+                Step::Node(id, inline) => (id, inline),
+                Step::Block(tail) => {
+                    ElemTail::render(tail, w.borrow_mut2())?;
+                    continue;
+                }
+                Step::Inline(tail) => {
+                    ElemTail::render(tail, w.borrow_mut2())?;
+                    continue;
+                }
+            };
+            let entry = &self.entries[id.0];
+            let children = &entry.children;
+            // Whitespace between blocks is replaced by the layout.
+            let keep = |c: &&NodeId| inline || !self.is_whitespace(**c);
+            match &entry.data {
+                Data::Root => stack.extend(
+                    children
+                        .iter()
+                        .rev()
+                        .filter(keep)
+                        .map(|&c| Step::Node(c, false)),
+                ),
+                Data::Element {
+                    name,
+                    attrs,
+                    self_closing,
+                } => {
+                    let elem = build::elem(name).with(AttrList(attrs));
+                    if !children.iter().any(|c| keep(&c)) {
+                        if *self_closing {
+                            w.render_inner(build::single(name).with(AttrList(attrs)))?;
+                        } else {
+                            w.render_inner(elem.inline())?;
+                        }
+                        continue;
+                    }
+                    // Laying out text would change it, so the element is kept as it is.
+                    let inline = inline || children.iter().any(|&c| self.is_content(c));
+                    if inline {
+                        stack.push(Step::Inline(elem.inline().render_head(w.borrow_mut2())?));
+                    } else {
+                        stack.push(Step::Block(elem.render_head(w.borrow_mut2())?));
+                    }
+                    stack.extend(
+                        children
+                            .iter()
+                            .rev()
+                            .filter(|&&c| inline || !self.is_whitespace(c))
+                            .map(|&c| Step::Node(c, inline)),
+                    );
+                }
+                Data::Text(t) => w.render_inner(build::raw(t))?,
+                Data::Comment(t) => w.render_inner(Comment::new(t))?,
+                Data::Raw(t) if is_declaration(t, w.2.html) => {
+                    w.render_inner(build::raw_escapable(t))?
+                }
+                Data::Raw(t) => {
+                    let e = Error::validation(format!("raw markup {:?} is not a declaration", t));
+                    return Err(w.2.fail(e));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn is_whitespace(&self, id: NodeId) -> bool {
+        matches!(&self.entries[id.0].data, Data::Text(t) if t.trim().is_empty())
+    }
+
+    fn is_content(&self, id: NodeId) -> bool {
+        match &self.entries[id.0].data {
+            Data::Text(t) | Data::Raw(t) => !t.trim().is_empty(),
+            _ => false,
+        }
+    }
+}
+
+///
+/// Whether raw markup is a single declaration or processing instruction, which is all
+/// a parsed or captured document holds, so it can be written as is.
+///
+fn is_declaration(markup: &str, html: bool) -> bool {
+    let end = if html {
+        // Html ends both at the first `>`.
+        markup.find('>').map(|i| i + 1)
+    } else if markup.starts_with("<?") {
+        markup.find("?>").map(|i| i + 2)
+    } else {
+        parse::declaration_len(markup)
+    };
+    if end != Some(markup.len()) {
+        return false;
+    }
+    if markup.starts_with("<?") {
+        return markup.ends_with("?>");
+    }
+    // Only an internal subset may hold markup.
+    let body = markup.get(2..).unwrap_or_default();
+    let before = body.split('[').next().unwrap_or_default();
+    let after = body.rsplit(']').next().unwrap_or_default();
+    markup.starts_with("<!")
+        && !markup.starts_with("<!--")
+        && !before.contains('<')
+        && !after.contains('<')
+}
+
+struct AttrList<'a>(&'a [(String, String)]);
+
+impl Attr for AttrList<'_> {
+    fn render(self, w: &mut AttrWrite) -> fmt::Result {
+        for (name, value) in self.0 {
+            w.render((name, value))?;
+        }
+        Ok(())
+    }
+}
+
+///
+/// A node of a [`Document`] and everything below it, as an element.
+///
+#[derive(Copy, Clone)]
+#[must_use]
+pub struct Subtree<'a> {
+    doc: &'a Document,
+    id: NodeId,
+}
+
+impl Locked for Subtree<'_> {}
+impl Elem for Subtree<'_> {
+    type Tail = ();
+    fn render_head(self, mut w: ElemWrite) -> Result<Self::Tail, fmt::Error> {
+        self.doc.render_node(self.id, &mut w)
+    }
+}
+
+impl Locked for &Document {}
+impl Elem for &Document {
+    type Tail = ();
+    fn render_head(self, w: ElemWrite) -> Result<Self::Tail, fmt::Error> {
+        self.subtree(self.root()).render_head(w)
+    }
+}
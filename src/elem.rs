@@ -2,24 +2,31 @@
 //! Elem trait and building blocks
 //!
 
-use crate::render::PrettyFmt;
+use crate::context::Context;
+use crate::render::Fmt;
+use crate::sink::{RawWriter, Sink, TextSink, TextWriter, WhitespaceWriter};
+use std::borrow::Cow;
 
 use super::*;
 
 ///
 /// Writer struct passed to escapable closure elem
 ///
-pub struct ElemWriteEscapable<'a>(WriteWrap<'a>, pub(crate) &'a mut PrettyFmt);
+pub struct ElemWriteEscapable<'a>(
+    pub(crate) &'a mut dyn Sink,
+    pub(crate) &'a mut dyn Fmt,
+    pub(crate) &'a mut Context,
+);
 
 impl<'a> ElemWriteEscapable<'a> {
     pub fn borrow_mut2(&mut self) -> ElemWriteEscapable<'_> {
-        ElemWriteEscapable(self.0.borrow_mut(), self.1)
+        ElemWriteEscapable(self.0, self.1, self.2)
     }
-    pub fn writer_escapable(&mut self) -> WriteWrap<'_> {
-        self.0.borrow_mut()
+    pub fn writer_escapable(&mut self) -> RawWriter<'_> {
+        RawWriter(self.0, self.2)
     }
-    pub fn writer(&mut self) -> tools::EscapeGuard<WriteWrap<'_>> {
-        tools::escape_guard(self.0.borrow_mut())
+    pub fn writer(&mut self) -> TextWriter<'_> {
+        TextWriter(self.0, self.2)
     }
 
     #[deprecated(note = "use tagu::session")]
@@ -28,7 +35,7 @@ impl<'a> ElemWriteEscapable<'a> {
         tail.render(self.as_elem_write())
     }
     fn as_elem_write(&mut self) -> ElemWrite<'_> {
-        ElemWrite(WriteWrap(self.0 .0), self.1)
+        ElemWrite(self.0, self.1, self.2)
     }
 
     #[deprecated(note = "use tagu::session")]
@@ -63,15 +70,19 @@ impl<'a> ElemWriteEscapable<'a> {
 /// Writer struct passed to closure elem
 ///
 #[must_use]
-pub struct ElemWrite<'a>(pub(crate) WriteWrap<'a>, pub(crate) &'a mut PrettyFmt);
+pub struct ElemWrite<'a>(
+    pub(crate) &'a mut dyn Sink,
+    pub(crate) &'a mut dyn Fmt,
+    pub(crate) &'a mut Context,
+);
 
 impl<'a> ElemWrite<'a> {
     pub(crate) fn borrow_mut2(&mut self) -> ElemWrite<'_> {
-        ElemWrite(self.0.borrow_mut(), self.1)
+        ElemWrite(self.0, self.1, self.2)
     }
 
-    pub fn writer(&mut self) -> tools::EscapeGuard<WriteWrap<'_>> {
-        tools::escape_guard(self.0.borrow_mut())
+    pub fn writer(&mut self) -> TextWriter<'_> {
+        TextWriter(self.0, self.2)
     }
 
     #[deprecated(note = "use tagu::session")]
@@ -94,10 +105,8 @@ impl<'a> ElemWrite<'a> {
         }
     }
 
-    fn swap_tab_type(&mut self, newt: &'static str) -> &'static str {
-        let ret = self.1.tab_char;
-        self.1.tab_char = newt;
-        ret
+    fn swap_tab_type(&mut self, newt: Cow<'static, str>) -> Cow<'static, str> {
+        self.1.swap_tab(newt)
     }
 
     #[deprecated(note = "use tagu::session")]
@@ -121,7 +130,7 @@ impl<'a> ElemWrite<'a> {
     }
 
     fn tabs(&mut self) -> fmt::Result {
-        self.1.tabs(&mut self.0)
+        self.1.tabs(&mut WhitespaceWriter(self.0, self.2))
     }
     fn push(&mut self) {
         self.1.push()
@@ -130,18 +139,163 @@ impl<'a> ElemWrite<'a> {
         self.1.pop()
     }
     fn end_tag(&mut self) -> fmt::Result {
-        self.1.end_tag(&mut self.0)
+        self.1.end_tag(&mut WhitespaceWriter(self.0, self.2))
     }
 
     pub(crate) fn as_escapable(&mut self) -> ElemWriteEscapable<'_> {
-        ElemWriteEscapable(WriteWrap(self.0 .0), self.1)
+        ElemWriteEscapable(self.0, self.1, self.2)
     }
-    pub(crate) fn writer_escapable(&mut self) -> WriteWrap<'_> {
-        self.0.borrow_mut()
+    pub(crate) fn writer_escapable(&mut self) -> RawWriter<'_> {
+        RawWriter(self.0, self.2)
+    }
+
+    ///
+    /// Write `<{start}{tag} attrs{ending}>` as markup, for declarations that are not elements.
+    ///
+    fn declaration(
+        &mut self,
+        start: impl fmt::Display,
+        tag: impl fmt::Display,
+        attr: impl Attr,
+        ending: impl fmt::Display,
+    ) -> fmt::Result {
+        let ctx = &mut *self.2;
+        let mut markup = String::new();
+        write!(markup, "<{}{}", start, tag)?;
+        let valid = markup[1..]
+            .chars()
+            .all(|c| tools::is_xml_char(c) && c != '<' && c != '>');
+        if !valid {
+            let e = Error::validation(format!("invalid declaration {:?}", &markup[1..]));
+            return Err(ctx.fail(e));
+        }
+
+        // Only for the error path; the declaration is never handed to the sink as a tag.
+        ctx.push_declaration(&markup[1..])?;
+        let mut sink = DeclarationSink(&mut markup);
+        let res = attr
+            .render(&mut AttrWrite::new(&mut sink, ctx))
+            .and_then(|_| ctx.flush_attrs(&mut sink).map_err(|e| ctx.fail(e)));
+        ctx.pop_tag();
+        res?;
+
+        write!(markup, "{}>", ending)?;
+        self.0.raw(&markup).map_err(|e| self.2.fail(e))
+    }
+
+    ///
+    /// Record an open tag and start it.
+    ///
+    fn open_tag(&mut self, tag: impl fmt::Display) -> fmt::Result {
+        self.2
+            .check_not_raw_text("elements")
+            .map_err(|e| self.2.fail(e))?;
+        let res = match self.2.push_tag(tag) {
+            Ok(()) => self.2.start_tag(self.0).map_err(|e| self.2.fail(e)),
+            Err(e) => Err(e),
+        };
+        // A tag that failed to open is never closed, so leave the path as it was.
+        if res.is_err() {
+            self.2.pop_tag();
+        }
+        res
+    }
+
+    fn close_tag(&mut self) {
+        self.2.pop_tag()
+    }
+
+    fn start_tag_end(&mut self) -> fmt::Result {
+        self.2
+            .flush_attrs(self.0)
+            .and_then(|_| self.0.start_tag_end())
+            .map_err(|e| self.2.fail(e))?;
+        self.2.start_content();
+        Ok(())
+    }
+
+    fn empty_tag_end(&mut self, ending: impl fmt::Display) -> fmt::Result {
+        self.2.flush_attrs(self.0).map_err(|e| self.2.fail(e))?;
+        self.2.name.clear();
+        write!(self.2.name, "{}", ending)?;
+        self.0
+            .empty_tag_end(&self.2.name)
+            .map_err(|e| self.2.fail(e))
+    }
+
+    fn end_element(&mut self, tag: impl fmt::Display) -> fmt::Result {
+        self.2.name.clear();
+        write!(self.2.name, "{}", tag)?;
+        self.0.end_tag(&self.2.name).map_err(|e| self.2.fail(e))
+    }
+
+    fn doctype(&mut self, doctype: impl fmt::Display) -> fmt::Result {
+        let ctx = &mut *self.2;
+        ctx.value.clear();
+        write!(ctx.value, "{}", doctype)?;
+        let valid = ctx
+            .value
+            .split_whitespace()
+            .next()
+            .is_some_and(tools::is_xml_name)
+            && ctx
+                .value
+                .chars()
+                .all(|c| tools::is_xml_char(c) && c != '<' && c != '>');
+        if !valid {
+            let e = Error::validation(format!("invalid doctype {:?}", ctx.value));
+            return Err(ctx.fail(e));
+        }
+        self.0.doctype(&ctx.value).map_err(|e| ctx.fail(e))
+    }
+
+    ///
+    /// Emit the processing instruction in the name and value of the context.
+    ///
+    fn processing_instruction(&mut self) -> fmt::Result {
+        let ctx = &mut *self.2;
+        if !tools::is_xml_name(&ctx.name) {
+            let e = Error::validation(format!(
+                "{:?} is not a valid processing instruction target",
+                ctx.name
+            ));
+            return Err(ctx.fail(e));
+        }
+        if ctx.value.contains("?>") || !ctx.value.chars().all(tools::is_xml_char) {
+            let e = Error::validation(format!(
+                "invalid processing instruction data {:?}",
+                ctx.value
+            ));
+            return Err(ctx.fail(e));
+        }
+        self.0
+            .processing_instruction(&ctx.name, &ctx.value)
+            .map_err(|e| ctx.fail(e))
+    }
+
+    fn cdata(&mut self, text: impl fmt::Display) -> fmt::Result {
+        // HTML has no CDATA sections in script or style, so it is just more of the raw text.
+        if self.2.is_raw_text() && self.2.html {
+            return write!(self.writer(), "{}", text);
+        }
+        self.2.value.clear();
+        write!(self.2.value, "{}", text)?;
+        self.0.cdata(&self.2.value).map_err(|e| self.2.fail(e))
+    }
+
+    fn comment(&mut self, text: impl fmt::Display) -> fmt::Result {
+        let ctx = &mut *self.2;
+        if ctx.html {
+            ctx.check_not_raw_text("comments")
+                .map_err(|e| ctx.fail(e))?;
+        }
+        ctx.value.clear();
+        write!(ctx.value, "{}", text)?;
+        self.0.comment(&ctx.value).map_err(|e| ctx.fail(e))
     }
 
     fn as_attr_write(&mut self) -> AttrWrite<'_> {
-        attr::AttrWrite::new(self.0.borrow_mut())
+        attr::AttrWrite::new(self.0, self.2)
     }
 
     // fn new(w: &'a mut dyn fmt::Write, fmt: &'a mut dyn Fmt) -> Self {
@@ -554,6 +708,185 @@ impl<D: fmt::Display> Elem for Raw<D> {
     }
 }
 
+///
+/// A comment element
+///
+#[derive(Copy, Clone)]
+#[must_use]
+pub struct Comment<D> {
+    text: D,
+}
+impl<D: fmt::Display> Comment<D> {
+    pub fn new(text: D) -> Comment<D> {
+        Comment { text }
+    }
+}
+
+impl<D: fmt::Display> Locked for Comment<D> {}
+impl<D: fmt::Display> Elem for Comment<D> {
+    type Tail = ();
+    fn render_head(self, mut w: ElemWrite) -> Result<Self::Tail, fmt::Error> {
+        w.tabs()?;
+        w.comment(self.text)?;
+        w.end_tag()?;
+        Ok(())
+    }
+}
+
+///
+/// A CDATA section
+///
+#[derive(Copy, Clone)]
+#[must_use]
+pub struct Cdata<D> {
+    text: D,
+}
+impl<D: fmt::Display> Cdata<D> {
+    pub fn new(text: D) -> Cdata<D> {
+        Cdata { text }
+    }
+}
+
+impl<D: fmt::Display> Locked for Cdata<D> {}
+impl<D: fmt::Display> Elem for Cdata<D> {
+    type Tail = ();
+    fn render_head(self, mut w: ElemWrite) -> Result<Self::Tail, fmt::Error> {
+        w.tabs()?;
+        w.cdata(self.text)?;
+        w.end_tag()?;
+        Ok(())
+    }
+}
+
+///
+/// A document type declaration
+///
+#[derive(Copy, Clone)]
+#[must_use]
+pub struct Doctype<D> {
+    doctype: D,
+}
+impl<D: fmt::Display> Doctype<D> {
+    pub fn new(doctype: D) -> Doctype<D> {
+        Doctype { doctype }
+    }
+}
+
+impl<D: fmt::Display> Locked for Doctype<D> {}
+impl<D: fmt::Display> Elem for Doctype<D> {
+    type Tail = ();
+    fn render_head(self, mut w: ElemWrite) -> Result<Self::Tail, fmt::Error> {
+        w.tabs()?;
+        w.doctype(self.doctype)?;
+        w.end_tag()?;
+        Ok(())
+    }
+}
+
+///
+/// The XML declaration, `<?xml version="1.0"?>`
+///
+#[derive(Copy, Clone)]
+#[must_use]
+pub struct XmlDeclaration<E> {
+    encoding: Option<E>,
+    standalone: Option<bool>,
+}
+impl XmlDeclaration<&'static str> {
+    pub fn new() -> Self {
+        XmlDeclaration {
+            encoding: None,
+            standalone: None,
+        }
+    }
+}
+impl Default for XmlDeclaration<&'static str> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<E: fmt::Display> XmlDeclaration<E> {
+    pub fn encoding<F: fmt::Display>(self, encoding: F) -> XmlDeclaration<F> {
+        XmlDeclaration {
+            encoding: Some(encoding),
+            standalone: self.standalone,
+        }
+    }
+    pub fn standalone(mut self, standalone: bool) -> Self {
+        self.standalone = Some(standalone);
+        self
+    }
+}
+
+impl<E: fmt::Display> Locked for XmlDeclaration<E> {}
+impl<E: fmt::Display> Elem for XmlDeclaration<E> {
+    type Tail = ();
+    fn render_head(self, mut w: ElemWrite) -> Result<Self::Tail, fmt::Error> {
+        w.tabs()?;
+        let ctx = &mut *w.2;
+        ctx.name.clear();
+        ctx.name.push_str("xml");
+        ctx.value.clear();
+        ctx.value.push_str("version=\"1.0\"");
+        if let Some(encoding) = self.encoding {
+            let start = ctx.value.len() + " encoding=\"".len();
+            write!(ctx.value, " encoding=\"{}\"", encoding)?;
+            // The EncName production of XML 1.0.
+            let name = &ctx.value[start..ctx.value.len() - 1];
+            let valid = name.starts_with(|c: char| c.is_ascii_alphabetic())
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "._-".contains(c));
+            if !valid {
+                let e = Error::validation(format!("invalid encoding name {:?}", name));
+                return Err(ctx.fail(e));
+            }
+        }
+        if let Some(standalone) = self.standalone {
+            let value = if standalone { "yes" } else { "no" };
+            write!(ctx.value, " standalone=\"{}\"", value)?;
+        }
+        w.processing_instruction()?;
+        w.end_tag()?;
+        Ok(())
+    }
+}
+
+///
+/// A processing instruction, such as `<?xml-stylesheet href="style.css"?>`
+///
+#[derive(Copy, Clone)]
+#[must_use]
+pub struct ProcessingInstruction<T, D> {
+    target: T,
+    data: D,
+}
+impl<T: fmt::Display, D: fmt::Display> ProcessingInstruction<T, D> {
+    pub fn new(target: T, data: D) -> ProcessingInstruction<T, D> {
+        ProcessingInstruction { target, data }
+    }
+}
+
+impl<T: fmt::Display, D: fmt::Display> Locked for ProcessingInstruction<T, D> {}
+impl<T: fmt::Display, D: fmt::Display> Elem for ProcessingInstruction<T, D> {
+    type Tail = ();
+    fn render_head(self, mut w: ElemWrite) -> Result<Self::Tail, fmt::Error> {
+        w.tabs()?;
+        let ctx = &mut *w.2;
+        ctx.name.clear();
+        write!(ctx.name, "{}", self.target)?;
+        if ctx.name.eq_ignore_ascii_case("xml") {
+            let e = Error::validation("the xml target is reserved, use build::xml_declaration");
+            return Err(ctx.fail(e));
+        }
+        ctx.value.clear();
+        write!(ctx.value, "{}", self.data)?;
+        w.processing_instruction()?;
+        w.end_tag()?;
+        Ok(())
+    }
+}
+
 // impl<'a> Locked for &'a str {}
 // impl<'a> Elem for &'a str {
 //     type Tail = ();
@@ -587,6 +920,62 @@ impl<D: fmt::Display> Elem for RawEscapable<D> {
 
 use fmt::Write;
 
+///
+/// Collects the attributes of a declaration as markup.
+///
+struct DeclarationSink<'a>(&'a mut String);
+
+impl DeclarationSink<'_> {
+    fn name(&mut self, name: &str) -> Result<(), Error> {
+        if !tools::is_xml_name(name) {
+            return Err(Error::validation(format!(
+                "{:?} is not a valid attribute name",
+                name
+            )));
+        }
+        self.0.push(' ');
+        self.0.push_str(name);
+        Ok(())
+    }
+}
+
+impl Sink for DeclarationSink<'_> {
+    fn start_tag(&mut self, _: &str) -> Result<(), Error> {
+        Err(Error::validation("a declaration cannot contain elements"))
+    }
+    fn attribute(&mut self, name: &str, value: &str) -> Result<(), Error> {
+        self.name(name)?;
+        self.0.push_str("=\"");
+        tools::escape_guard(&mut *self.0)
+            .attribute()
+            .write_str(value)
+            .map_err(|_| Error::validation(format!("invalid attribute value {:?}", value)))?;
+        self.0.push('"');
+        Ok(())
+    }
+    fn flag(&mut self, name: &str) -> Result<(), Error> {
+        self.name(name)
+    }
+    fn start_tag_end(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+    fn empty_tag_end(&mut self, _: &str) -> Result<(), Error> {
+        Ok(())
+    }
+    fn end_tag(&mut self, _: &str) -> Result<(), Error> {
+        Ok(())
+    }
+    fn text(&mut self, _: &str) -> Result<(), Error> {
+        Err(Error::validation("a declaration cannot contain text"))
+    }
+    fn raw(&mut self, _: &str) -> Result<(), Error> {
+        Err(Error::validation("a declaration cannot contain markup"))
+    }
+    fn comment(&mut self, _: &str) -> Result<(), Error> {
+        Err(Error::validation("a declaration cannot contain comments"))
+    }
+}
+
 ///
 /// A element with no ending tag
 ///
@@ -617,6 +1006,13 @@ impl<D: fmt::Display, A: Attr, K, Z> Single<D, A, K, Z> {
             start: self.start,
         }
     }
+
+    ///
+    /// Put something in front of the tag, as in `<!DOCTYPE html>`.
+    ///
+    /// A start of `!` or `?` makes a declaration, which is written as markup without
+    /// checking the tag as a name. Any other start is part of the element name.
+    ///
     pub fn with_start<KK: fmt::Display>(self, start: KK) -> Single<D, A, KK, Z> {
         Single {
             tag: self.tag,
@@ -636,12 +1032,17 @@ impl<D: fmt::Display, A: Attr, K: fmt::Display, Z: fmt::Display> Elem for Single
             ending,
         } = self;
         w.tabs()?;
-        w.writer_escapable().write_char('<')?;
-        write!(w.writer(), "{}{}", start, tag)?;
-        //w.writer().write_char(' ')?;
-        attr.render(&mut w.as_attr_write())?;
-        write!(w.writer(), "{}", ending)?;
-        w.writer_escapable().write_str(">")?;
+        let start = start.to_string();
+        if start.starts_with(['!', '?']) {
+            w.declaration(&start, tag, attr, ending)?;
+            return w.end_tag();
+        }
+        w.open_tag(format_args!("{}{}", start, tag))?;
+        let res = attr
+            .render(&mut w.as_attr_write())
+            .and_then(|_| w.empty_tag_end(ending));
+        w.close_tag();
+        res?;
         w.end_tag()?;
         Ok(())
     }
@@ -674,9 +1075,8 @@ impl<D: fmt::Display> ElemTail for ElementTail<D> {
         //w.end_tag()?;
         w.tabs()?;
 
-        w.writer_escapable().write_str("</")?;
-        write!(w.writer(), "{}", &self.tag)?;
-        w.writer_escapable().write_char('>')?;
+        w.end_element(&self.tag)?;
+        w.close_tag();
         w.end_tag()?;
 
         Ok(())
@@ -725,7 +1125,7 @@ impl<E: Elem> Elem for Inliner<E> {
 
 pub struct WithTabTail<T> {
     tail: T,
-    original: &'static str,
+    original: Cow<'static, str>,
 }
 impl<T: ElemTail> ElemTail for WithTabTail<T> {
     fn render(self, mut w: ElemWrite) -> std::fmt::Result {
@@ -744,7 +1144,7 @@ impl<E> Locked for WithTab<E> {}
 impl<E: Elem> Elem for WithTab<E> {
     type Tail = WithTabTail<E::Tail>;
     fn render_head(self, mut w: ElemWrite) -> Result<Self::Tail, fmt::Error> {
-        let original = w.swap_tab_type(self.new_tab);
+        let original = w.swap_tab_type(Cow::Borrowed(self.new_tab));
         let tail = self.elem.render_head(w.borrow_mut2())?;
 
         Ok(WithTabTail { original, tail })
@@ -776,11 +1176,14 @@ impl<D: fmt::Display, A: Attr> Elem for Element<D, A> {
     fn render_head(self, mut w: ElemWrite) -> Result<Self::Tail, fmt::Error> {
         let Element { tag, attr } = self;
         w.tabs()?;
-        w.writer_escapable().write_char('<')?;
-        write!(w.writer(), "{}", tag)?;
-        //w.writer().write_char(' ')?;
-        attr.render(&mut w.as_attr_write())?;
-        w.writer_escapable().write_str(">")?;
+        w.open_tag(&tag)?;
+        if let Err(e) = attr
+            .render(&mut w.as_attr_write())
+            .and_then(|_| w.start_tag_end())
+        {
+            w.close_tag();
+            return Err(e);
+        }
 
         w.end_tag()?;
 
@@ -810,12 +1213,16 @@ pub struct BufferedElem {
 }
 
 impl BufferedElem {
-    pub fn new<E: Elem + Locked>(elem: E) -> Result<Self, fmt::Error> {
+    pub fn new<E: Elem + Locked>(elem: E) -> Result<Self, Error> {
         let mut fmt = crate::render::PrettyFmt::new();
-        let mut head = String::new();
-        let mut tail = String::new();
-        let t = elem.render_head(ElemWrite(WriteWrap(&mut head), &mut fmt))?;
-        t.render(ElemWrite(WriteWrap(&mut tail), &mut fmt))?;
+        let mut ctx = Context::new(&crate::render::RenderOptions::new());
+        let mut head = TextSink::new(String::new());
+        let mut tail = TextSink::new(String::new());
+        elem.render_head(ElemWrite(&mut head, &mut fmt, &mut ctx))
+            .and_then(|t| t.render(ElemWrite(&mut tail, &mut fmt, &mut ctx)))
+            .map_err(|e| ctx.take_error(e))?;
+        let mut head = head.finish()?;
+        let mut tail = tail.finish()?;
         head.shrink_to_fit();
         tail.shrink_to_fit();
         Ok(BufferedElem { head, tail })
@@ -837,7 +1244,7 @@ impl<'a> ElemTail for BufferedTail<'a> {
         write!(w.writer_escapable(), "{}", self.tail)
     }
 }
-impl<'a> Locked for &'a BufferedElem {}
+impl Locked for &BufferedElem {}
 
 impl<'a> Elem for &'a BufferedElem {
     type Tail = BufferedTail<'a>;
@@ -847,6 +1254,14 @@ impl<'a> Elem for &'a BufferedElem {
     }
 }
 
+impl Locked for () {}
+impl Elem for () {
+    type Tail = ();
+    fn render_head(self, _: ElemWrite) -> Result<Self::Tail, fmt::Error> {
+        Ok(())
+    }
+}
+
 impl ElemTail for () {
     fn render(self, _: ElemWrite) -> std::fmt::Result {
         Ok(())
@@ -0,0 +1,136 @@
+//!
+//! The error type returned when rendering fails
+//!
+
+use std::fmt;
+use std::io;
+
+/// A boxed error, as returned by user code.
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+///
+/// The underlying cause of an [`Error`].
+///
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The writer returned a `fmt::Error`.
+    Fmt,
+    /// An io error from the underlying writer.
+    Io(io::Error),
+    /// The elements described invalid markup.
+    Validation(String),
+    /// An error returned by user code inside a session.
+    User(BoxError),
+}
+
+///
+/// Error returned when rendering fails. Along with the cause, it records
+/// the names of the tags that were open at the point of failure, outermost first.
+///
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    path: Vec<String>,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind) -> Self {
+        Error {
+            kind,
+            path: Vec::new(),
+        }
+    }
+
+    pub fn validation(msg: impl Into<String>) -> Self {
+        Error::new(ErrorKind::Validation(msg.into()))
+    }
+
+    pub fn user(err: impl Into<BoxError>) -> Self {
+        Error::new(ErrorKind::User(err.into()))
+    }
+
+    pub(crate) fn from_boxed(e: BoxError) -> Self {
+        match e.downcast::<Error>() {
+            Ok(e) => *e,
+            Err(e) => Error::user(e),
+        }
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    pub fn into_kind(self) -> ErrorKind {
+        self.kind
+    }
+
+    ///
+    /// The tags that were open when the error occurred, outermost first.
+    ///
+    pub fn path(&self) -> &[String] {
+        &self.path
+    }
+
+    pub(crate) fn with_path(mut self, path: impl FnOnce() -> Vec<String>) -> Self {
+        if self.path.is_empty() {
+            self.path = path();
+        }
+        self
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            ErrorKind::Fmt => write!(f, "an error occurred when formatting")?,
+            ErrorKind::Io(e) => write!(f, "io error: {}", e)?,
+            ErrorKind::Validation(msg) => write!(f, "{}", msg)?,
+            ErrorKind::User(e) => write!(f, "{}", e)?,
+        }
+        if !self.path.is_empty() {
+            write!(f, " (at {})", self.path.join(" > "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ErrorKind::Io(e) => Some(e),
+            ErrorKind::User(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<fmt::Error> for Error {
+    fn from(_: fmt::Error) -> Self {
+        Error::new(ErrorKind::Fmt)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::new(ErrorKind::Io(e))
+    }
+}
+
+///
+/// Gives back the original `io::Error` if that was the cause.
+///
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        match e.kind {
+            ErrorKind::Io(e) => e,
+            _ => io::Error::other(e),
+        }
+    }
+}
+
+impl From<Error> for fmt::Error {
+    fn from(_: Error) -> Self {
+        fmt::Error
+    }
+}
@@ -7,12 +7,19 @@ pub mod tools;
 use std::fmt;
 pub mod attr;
 pub mod build;
+mod context;
+pub mod dom;
 pub mod elem;
+pub mod error;
+pub mod ns;
+pub mod parse;
 use attr::*;
-mod render;
+pub use error::Error;
+pub mod render;
+pub mod select;
+pub mod sink;
 pub mod stack;
 use elem::*;
-use tools::WriteWrap;
 
 pub mod prelude {
     //! The tagu prelude
@@ -25,24 +32,81 @@ pub mod prelude {
 pub mod util {
     use super::*;
     pub fn comment(a: impl fmt::Display) -> impl Elem + Locked {
-        build::single(a).with_start("!--").with_ending("--")
+        Comment::new(a)
     }
 }
 
 ///
 /// Render elements to a writer
 ///
-pub fn render<E: Elem + Locked, W: fmt::Write>(elem: E, writer: W) -> fmt::Result {
+pub fn render<E: Elem + Locked, W: fmt::Write>(elem: E, writer: W) -> Result<(), Error> {
     render::Renderer::new().render(elem, writer)
 }
 
 ///
 /// Render elements to a writer that allows for escaping elements.
 ///
-pub fn render_escapable<E: Elem, W: fmt::Write>(elem: E, writer: W) -> fmt::Result {
+pub fn render_escapable<E: Elem, W: fmt::Write>(elem: E, writer: W) -> Result<(), Error> {
     render::Renderer::new().render_escapable(elem, writer)
 }
 
+///
+/// Render elements as events into a [`sink::Sink`] instead of text.
+///
+pub fn render_sink<E: Elem + Locked>(elem: E, sink: &mut dyn sink::Sink) -> Result<(), Error> {
+    render::Renderer::new().render_sink(elem, sink)
+}
+
+///
+/// Render elements to a `std::io::Write`.
+///
+/// Output is buffered and flushed once rendering is done.
+/// If the writer fails, its `io::Error` is returned as is.
+///
+/// ```
+/// let mut v = Vec::new();
+/// tagu::render_io(tagu::build::single("hello"), &mut v).unwrap();
+/// assert_eq!(v, b"<hello/>\n");
+/// ```
+///
+pub fn render_io<E: Elem + Locked, W: std::io::Write>(elem: E, writer: W) -> std::io::Result<()> {
+    render::Renderer::new().render_io(elem, writer)
+}
+
+///
+/// Render elements to a file.
+///
+/// The output is first written to a temporary file next to `path`, which is then renamed over it.
+/// So `path` either keeps its old contents or gets the complete new ones.
+///
+/// ```
+/// let path = std::env::temp_dir().join("tagu_render_file.svg");
+/// tagu::render_file(tagu::build::single("svg"), &path).unwrap();
+/// assert_eq!(std::fs::read_to_string(&path).unwrap(), "<svg/>\n");
+/// ```
+///
+pub fn render_file<E: Elem + Locked, P: AsRef<std::path::Path>>(
+    elem: E,
+    path: P,
+) -> std::io::Result<()> {
+    render::Renderer::new().render_file(elem, path)
+}
+
+///
+/// Render elements to an async writer. See [`render::Renderer::render_async`].
+///
+pub async fn render_async<E, I, W>(root: E, children: I, writer: W) -> std::io::Result<()>
+where
+    E: Elem + Locked,
+    I: IntoIterator,
+    I::Item: Elem + Locked,
+    W: render::AsyncWrite + Unpin,
+{
+    render::Renderer::new()
+        .render_async(root, children, writer)
+        .await
+}
+
 ///
 /// An std out that implements fmt::Write
 ///
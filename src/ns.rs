@@ -0,0 +1,130 @@
+//!
+//! XML namespaces, declared on the element where they are first needed.
+//! Undeclared prefixes are only an error in the Xml and Xhtml modes.
+//!
+
+use super::*;
+use std::borrow::Cow;
+
+///
+/// A namespace, and the prefix to refer to it by. The prefix is empty for a default namespace.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Namespace {
+    prefix: Cow<'static, str>,
+    uri: Cow<'static, str>,
+}
+
+pub const SVG: Namespace = Namespace::new("", "http://www.w3.org/2000/svg");
+pub const XLINK: Namespace = Namespace::new("xlink", "http://www.w3.org/1999/xlink");
+pub const XHTML: Namespace = Namespace::new("", "http://www.w3.org/1999/xhtml");
+pub const MATHML: Namespace = Namespace::new("", "http://www.w3.org/1998/Math/MathML");
+
+///
+/// The namespace of `xml:lang` and `xml:space`, which is always in scope.
+///
+pub const XML: Namespace = Namespace::new("xml", "http://www.w3.org/XML/1998/namespace");
+
+impl Namespace {
+    pub const fn new(prefix: &'static str, uri: &'static str) -> Self {
+        Namespace {
+            prefix: Cow::Borrowed(prefix),
+            uri: Cow::Borrowed(uri),
+        }
+    }
+
+    pub fn from_parts(
+        prefix: impl Into<Cow<'static, str>>,
+        uri: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        Namespace {
+            prefix: prefix.into(),
+            uri: uri.into(),
+        }
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    ///
+    /// The name of something in this namespace, such as `xlink:href`.
+    ///
+    pub fn name<D: fmt::Display>(&self, local: D) -> QName<D> {
+        QName {
+            prefix: self.prefix.clone(),
+            local,
+        }
+    }
+}
+
+///
+/// A name with a namespace prefix
+///
+#[derive(Clone)]
+pub struct QName<D> {
+    prefix: Cow<'static, str>,
+    local: D,
+}
+
+impl<D: fmt::Display> fmt::Display for QName<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.prefix.is_empty() {
+            write!(f, "{}", self.local)
+        } else {
+            write!(f, "{}:{}", self.prefix, self.local)
+        }
+    }
+}
+
+///
+/// Declares a namespace on the element, unless it is already in scope
+///
+#[derive(Clone)]
+#[must_use]
+pub struct Declare {
+    ns: Namespace,
+}
+
+impl Declare {
+    pub fn new(ns: Namespace) -> Self {
+        Declare { ns }
+    }
+}
+
+impl Attr for Declare {
+    fn render(self, w: &mut AttrWrite) -> fmt::Result {
+        w.declare(&self.ns)
+    }
+}
+
+///
+/// An attribute in a namespace, which is declared if needed
+///
+#[derive(Clone)]
+#[must_use]
+pub struct NsAttr<D, V> {
+    ns: Namespace,
+    local: D,
+    value: V,
+}
+
+impl<D: fmt::Display, V: fmt::Display> NsAttr<D, V> {
+    pub fn new(ns: Namespace, local: D, value: V) -> Self {
+        NsAttr { ns, local, value }
+    }
+}
+
+impl<D: fmt::Display, V: fmt::Display> Attr for NsAttr<D, V> {
+    fn render(self, w: &mut AttrWrite) -> fmt::Result {
+        // Attributes without a prefix are in no namespace, whatever the default is.
+        if !self.ns.prefix.is_empty() {
+            w.declare(&self.ns)?;
+        }
+        w.render((self.ns.name(self.local), self.value))
+    }
+}
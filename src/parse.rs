@@ -0,0 +1,469 @@
+//!
+//! Parse existing XML or HTML markup into a [`Document`]
+//!
+
+use super::*;
+use crate::dom::{Document, NodeId};
+use std::borrow::Cow;
+
+mod entities;
+
+///
+/// Parse XML markup with the default options.
+///
+/// ```
+/// use tagu::build;
+/// use tagu::prelude::*;
+///
+/// let icon = tagu::parse::xml("<g><circle r='5'/><!-- dot --></g>").unwrap();
+/// let all = build::elem("svg").append(&icon);
+///
+/// let mut s = String::new();
+/// tagu::render(all, &mut s).unwrap();
+/// assert_eq!(s, "<svg>\n\t<g>\n\t\t<circle r=\"5\"/>\n\t\t<!-- dot -->\n\t</g>\n</svg>\n");
+/// ```
+///
+pub fn xml(src: &str) -> Result<Document, Error> {
+    ParseOptions::xml().parse(src)
+}
+
+///
+/// Parse HTML markup with the default options.
+///
+/// ```
+/// let doc = tagu::parse::html("<P class=a>Hi<br>there<input disabled></p>").unwrap();
+/// let p = doc.select("p.a").unwrap()[0];
+/// assert_eq!(doc.text(p), "Hithere");
+/// assert_eq!(doc.select("input[disabled]").unwrap().len(), 1);
+/// ```
+///
+pub fn html(src: &str) -> Result<Document, Error> {
+    ParseOptions::html().parse(src)
+}
+
+///
+/// Options for parsing markup. The XML flavour is strict, while the HTML flavour
+/// lowercases names, allows unquoted or missing attribute values and closes
+/// void elements and elements still open at the end.
+///
+#[derive(Clone, Debug)]
+#[must_use]
+pub struct ParseOptions {
+    html: bool,
+    trim_text: bool,
+}
+
+impl ParseOptions {
+    pub fn xml() -> Self {
+        ParseOptions {
+            html: false,
+            trim_text: false,
+        }
+    }
+
+    pub fn html() -> Self {
+        ParseOptions {
+            html: true,
+            trim_text: false,
+        }
+    }
+
+    ///
+    /// Remove leading and trailing whitespace from text, including `script` and
+    /// `style` content, and drop text that is only whitespace.
+    ///
+    pub fn trim_text(mut self, trim_text: bool) -> Self {
+        self.trim_text = trim_text;
+        self
+    }
+
+    pub fn parse(&self, src: &str) -> Result<Document, Error> {
+        let doc = Document::new();
+        let root = doc.root();
+        let mut p = Parser {
+            options: self,
+            src,
+            pos: 0,
+            doc,
+            open: vec![root],
+            text: String::new(),
+        };
+        match p.run() {
+            Ok(()) => Ok(p.doc),
+            Err(e) => {
+                let path = p.open[1..]
+                    .iter()
+                    .filter_map(|&id| p.doc.name(id).map(String::from))
+                    .collect();
+                Err(e.with_path(|| path))
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    options: &'a ParseOptions,
+    src: &'a str,
+    pos: usize,
+    doc: Document,
+    open: Vec<NodeId>,
+    text: String,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, msg: impl fmt::Display) -> Error {
+        let before = &self.src[..self.pos];
+        let line = before.matches('\n').count() + 1;
+        let column = before[before.rfind('\n').map_or(0, |i| i + 1)..]
+            .chars()
+            .count()
+            + 1;
+        Error::validation(format!("{} at line {}, column {}", msg, line, column))
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn skip_ws(&mut self) {
+        while let Some(c) = self.peek().filter(|c| c.is_whitespace()) {
+            self.pos += c.len_utf8();
+        }
+    }
+
+    ///
+    /// Consume everything up to and including `end`, returning what came before it.
+    ///
+    fn until(&mut self, end: &str, what: &str) -> Result<&'a str, Error> {
+        let len = self
+            .rest()
+            .find(end)
+            .ok_or_else(|| self.error(format_args!("unterminated {}", what)))?;
+        let start = self.pos;
+        self.pos += len + end.len();
+        Ok(&self.src[start..start + len])
+    }
+
+    fn name(&mut self) -> Result<String, Error> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() || "/>=<\"'".contains(c) {
+                break;
+            }
+            self.pos += c.len_utf8();
+        }
+        if start == self.pos {
+            return Err(self.error("expected a name"));
+        }
+        let name = &self.src[start..self.pos];
+        Ok(if self.options.html {
+            name.to_ascii_lowercase()
+        } else {
+            name.to_string()
+        })
+    }
+
+    fn current(&self) -> NodeId {
+        *self.open.last().unwrap()
+    }
+
+    fn append(&mut self, id: NodeId) {
+        self.flush_text();
+        let parent = self.current();
+        self.doc.append_child(parent, id);
+    }
+
+    fn flush_text(&mut self) {
+        let text = std::mem::take(&mut self.text);
+        let text = if self.options.trim_text {
+            text.trim()
+        } else {
+            &text
+        };
+        if !text.is_empty() {
+            let parent = self.current();
+            self.doc.append_text(parent, text);
+        }
+    }
+
+    fn run(&mut self) -> Result<(), Error> {
+        while !self.rest().is_empty() {
+            let rest = self.rest();
+            if rest.starts_with("<!--") {
+                self.pos += 4;
+                let text = self.until("-->", "comment")?;
+                let id = self.doc.create_comment(text);
+                self.append(id);
+            } else if rest.starts_with("<![CDATA[") {
+                self.pos += 9;
+                let text = self.until("]]>", "CDATA section")?;
+                self.text.push_str(text);
+            } else if rest.starts_with("<?") {
+                let start = self.pos;
+                self.until("?>", "processing instruction")?;
+                let id = self.doc.create_raw(&self.src[start..self.pos]);
+                self.append(id);
+            } else if rest.starts_with("<!") {
+                let start = self.pos;
+                self.declaration()?;
+                let id = self.doc.create_raw(&self.src[start..self.pos]);
+                self.append(id);
+            } else if rest.starts_with("</") {
+                self.pos += 2;
+                self.end_tag()?;
+            } else if rest.starts_with('<')
+                && rest[1..]
+                    .chars()
+                    .next()
+                    .is_some_and(|c| c.is_alphabetic() || c == '_' || c == ':')
+            {
+                self.pos += 1;
+                self.start_tag()?;
+            } else if rest.starts_with('<') && !self.options.html {
+                return Err(self.error("unexpected <"));
+            } else {
+                // Text always takes at least one char, so a stray `<` in html is kept.
+                let first = rest.chars().next().map_or(0, char::len_utf8);
+                let len = rest[first..].find('<').map_or(rest.len(), |i| i + first);
+                let text = self.decode(&rest[..len], false)?;
+                self.text.push_str(&text);
+                self.pos += len;
+            }
+        }
+        self.flush_text();
+        if !self.options.html && self.open.len() > 1 {
+            let name = self.doc.name(self.current()).unwrap_or_default();
+            return Err(self.error(format_args!("element {} is not closed", name)));
+        }
+        Ok(())
+    }
+
+    fn start_tag(&mut self) -> Result<(), Error> {
+        let name = self.name()?;
+        let id = self.doc.create_element(name.as_str());
+        self.append(id);
+        self.open.push(id);
+
+        loop {
+            self.skip_ws();
+            let rest = self.rest();
+            if rest.starts_with("/>") {
+                self.pos += 2;
+                self.open.pop();
+                return Ok(());
+            } else if rest.starts_with('>') {
+                self.pos += 1;
+                break;
+            } else if rest.starts_with('<') && self.options.html {
+                break;
+            } else if rest.is_empty() {
+                return Err(self.error("unterminated start tag"));
+            }
+
+            let attr = self.name()?;
+            self.skip_ws();
+            let value = if self.peek() == Some('=') {
+                self.pos += 1;
+                self.skip_ws();
+                self.attr_value()?
+            } else if self.options.html {
+                String::new()
+            } else {
+                return Err(self.error(format_args!("attribute {} has no value", attr)));
+            };
+            if self.doc.attr(id, &attr).is_none() {
+                self.doc.set_attr(id, attr, value);
+            } else if !self.options.html {
+                return Err(self.error(format_args!("duplicate attribute {}", attr)));
+            }
+        }
+
+        // Written with an end tag, or without one in the case of html void elements.
+        self.doc
+            .set_self_closing(id, self.options.html && sink::is_html_void(&name));
+        if self.options.html {
+            if sink::is_html_void(&name) {
+                self.open.pop();
+            } else if sink::is_html_raw_text(&name) {
+                let len = sink::find_raw_text_end(self.rest(), &name).unwrap_or(self.rest().len());
+                let mut content = &self.src[self.pos..self.pos + len];
+                if self.options.trim_text {
+                    content = content.trim();
+                }
+                // Kept as text, the element tells the sink it is raw text when rendering.
+                if !content.is_empty() {
+                    self.doc.append_text(id, content);
+                }
+                self.pos += len;
+            }
+        }
+        Ok(())
+    }
+
+    fn declaration(&mut self) -> Result<(), Error> {
+        match declaration_len(self.rest()) {
+            Some(len) => {
+                self.pos += len;
+                Ok(())
+            }
+            None => Err(self.error("unterminated declaration")),
+        }
+    }
+
+    fn attr_value(&mut self) -> Result<String, Error> {
+        match self.peek() {
+            Some(q @ ('"' | '\'')) => {
+                self.pos += 1;
+                let start = self.pos;
+                let len = self
+                    .rest()
+                    .find(q)
+                    .ok_or_else(|| self.error("unterminated attribute value"))?;
+                self.pos += len + 1;
+                self.decode(&self.src[start..start + len], true)
+            }
+            _ if self.options.html => {
+                let start = self.pos;
+                while let Some(c) = self.peek() {
+                    if c.is_whitespace() || c == '>' {
+                        break;
+                    }
+                    self.pos += c.len_utf8();
+                }
+                self.decode(&self.src[start..self.pos], true)
+            }
+            _ => Err(self.error("expected a quoted attribute value")),
+        }
+    }
+
+    fn end_tag(&mut self) -> Result<(), Error> {
+        let name = self.name()?;
+        self.skip_ws();
+        if self.peek() != Some('>') {
+            return Err(self.error("unterminated end tag"));
+        }
+        self.pos += 1;
+        self.flush_text();
+
+        let found = self.open[1..]
+            .iter()
+            .rposition(|&id| self.doc.name(id) == Some(name.as_str()));
+        match found {
+            Some(i) if self.options.html => self.open.truncate(i + 1),
+            Some(i) if i + 2 == self.open.len() => {
+                self.open.pop();
+            }
+            _ if self.options.html => {}
+            _ => {
+                let msg = match self.doc.name(self.current()) {
+                    Some(open) => format!("end tag {} does not match {}", name, open),
+                    None => format!("end tag {} without a start tag", name),
+                };
+                return Err(self.error(msg));
+            }
+        }
+        Ok(())
+    }
+
+    ///
+    /// Replace the character references in text or an attribute value.
+    ///
+    fn decode(&self, s: &str, attr: bool) -> Result<String, Error> {
+        let mut out = String::with_capacity(s.len());
+        let mut rest = s;
+        while let Some(i) = rest.find('&') {
+            out.push_str(&rest[..i]);
+            rest = &rest[i + 1..];
+            match self.reference(rest, attr) {
+                Some((len, value)) => {
+                    out.push_str(&value);
+                    rest = &rest[len..];
+                }
+                None if self.options.html => out.push('&'),
+                None => return Err(self.error("unknown or unterminated entity")),
+            }
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+
+    ///
+    /// The value of the character reference that `s` starts with, just after the `&`,
+    /// and how long it is.
+    ///
+    fn reference(&self, s: &str, attr: bool) -> Option<(usize, Cow<'static, str>)> {
+        if let Some(end) = s.find(';') {
+            if let Some(value) = entity(&s[..=end], self.options.html) {
+                return Some((end + 1, value));
+            }
+        }
+        if !self.options.html {
+            return None;
+        }
+        // Some old names also work without the `;`, the longest one that fits is taken.
+        let run = s
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(s.len());
+        let (len, value) = (1..=run)
+            .rev()
+            .find_map(|len| Some((len, entities::get(&s[..len])?)))?;
+        // Unless the name goes on in an attribute value, as in a URL query like `?a&copy=1`.
+        let next = s[len..].chars().next();
+        if attr && next.is_some_and(|c| c.is_ascii_alphanumeric() || c == '=') {
+            return None;
+        }
+        Some((len, Cow::Borrowed(value)))
+    }
+}
+
+///
+/// The length of the `<!...>` declaration that `s` starts with, skipping over quoted
+/// strings and a bracketed internal subset that may contain `>` themselves.
+///
+pub(crate) fn declaration_len(s: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut depth = 0usize;
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (Some(q), _) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '[') => depth += 1,
+            (None, ']') => depth = depth.saturating_sub(1),
+            (None, '>') if depth == 0 => return Some(i + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+///
+/// The value of a character reference, given without the `&` and with the `;`.
+///
+fn entity(name: &str, html: bool) -> Option<Cow<'static, str>> {
+    if let Some(num) = name.strip_prefix('#') {
+        let num = num.strip_suffix(';')?;
+        let code = match num.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => num.parse().ok()?,
+        };
+        let c = char::from_u32(code).filter(|&c| tools::is_xml_char(c))?;
+        return Some(Cow::Owned(c.to_string()));
+    }
+    if html {
+        return entities::get(name).map(Cow::Borrowed);
+    }
+    let value = match name {
+        "lt;" => "<",
+        "gt;" => ">",
+        "amp;" => "&",
+        "quot;" => "\"",
+        "apos;" => "'",
+        _ => return None,
+    };
+    Some(Cow::Borrowed(value))
+}
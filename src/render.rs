@@ -3,14 +3,48 @@
 //!
 
 use super::*;
+use crate::context::Context;
+use crate::ns::Namespace;
+use crate::sink::{Sink, TextOptions, TextSink, TextState};
+use crate::tools::{Escape, InvalidChars};
+use std::borrow::Cow;
+use std::future::poll_fn;
+use std::io::{self, Write as _};
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{self, Poll};
 
-pub struct Renderer {
-    fmt: PrettyFmt,
+///
+/// Renders elements using a particular formatter.
+///
+/// ```
+/// use tagu::build;
+/// use tagu::prelude::*;
+/// use tagu::render::{NoFmt, Renderer};
+///
+/// let all = build::elem("a").append(build::elem("b"));
+///
+/// let mut s = String::new();
+/// Renderer::new().with_fmt(NoFmt).render(all, &mut s).unwrap();
+/// assert_eq!(s, "<a><b></b></a>");
+/// ```
+///
+pub struct Renderer<F = PrettyFmt> {
+    fmt: F,
+    options: RenderOptions,
 }
 impl Renderer {
     pub fn new() -> Self {
+        Renderer::with_options(RenderOptions::new())
+    }
+
+    ///
+    /// Create a renderer that pretty prints according to the given options.
+    ///
+    pub fn with_options(options: RenderOptions) -> Self {
         Renderer {
-            fmt: PrettyFmt::new(),
+            fmt: PrettyFmt::with_options(&options),
+            options,
         }
     }
 }
@@ -21,40 +55,657 @@ impl Default for Renderer {
     }
 }
 
-impl Renderer {
-    // pub fn with_fmt<K: Fmt>(self, a: K) -> Renderer<K> {
-    //     Renderer { fmt: a }
-    // }
+impl<F: Fmt> Renderer<F> {
+    pub fn with_fmt<K: Fmt>(self, a: K) -> Renderer<K> {
+        Renderer {
+            fmt: a,
+            options: self.options,
+        }
+    }
     pub fn render<E: Elem + Locked, W: fmt::Write>(
         &mut self,
         elem: E,
         mut writer: W,
-    ) -> fmt::Result {
-        ElemWrite(WriteWrap(&mut writer), &mut self.fmt).render_inner(elem)
+    ) -> Result<(), Error> {
+        self.run(&mut writer, |mut e| e.render_inner(elem))
     }
     pub fn render_escapable<E: Elem, W: fmt::Write>(
         &mut self,
         elem: E,
         mut writer: W,
-    ) -> fmt::Result {
-        let mut e = ElemWrite(WriteWrap(&mut writer), &mut self.fmt);
-        let tail = elem.render_head(e.borrow_mut2())?;
-        tail.render(e)
+    ) -> Result<(), Error> {
+        self.run(&mut writer, |mut e| {
+            let tail = elem.render_head(e.borrow_mut2())?;
+            tail.render(e)
+        })
+    }
+
+    ///
+    /// Render elements as events into a [`Sink`]. See [`crate::render_sink`].
+    ///
+    pub fn render_sink<E: Elem + Locked>(
+        &mut self,
+        elem: E,
+        sink: &mut dyn Sink,
+    ) -> Result<(), Error> {
+        Steps::new(self).step_sink(sink, |mut e| e.render_inner(elem))
+    }
+
+    ///
+    /// Render elements to a `std::io::Write`. See [`crate::render_io`].
+    ///
+    pub fn render_io<E: Elem + Locked, W: io::Write>(
+        &mut self,
+        elem: E,
+        writer: W,
+    ) -> io::Result<()> {
+        let mut w = tools::upgrade_write(io::BufWriter::new(writer));
+        let res = self.render(elem, &mut w);
+        w.error?;
+        res?;
+        w.inner.flush()
+    }
+
+    ///
+    /// Render elements to a file. See [`crate::render_file`].
+    ///
+    pub fn render_file<E: Elem + Locked, P: AsRef<Path>>(
+        &mut self,
+        elem: E,
+        path: P,
+    ) -> io::Result<()> {
+        tools::write_file_atomic(path, |file| self.render_io(elem, file))
+    }
+
+    ///
+    /// Render elements to an [`AsyncWrite`] as with [`Renderer::chunks`], awaiting the writer
+    /// after each chunk. It is given at most [`RenderOptions::chunk_size`] bytes at a time.
+    ///
+    pub async fn render_async<E, I, W>(
+        &mut self,
+        root: E,
+        children: I,
+        mut writer: W,
+    ) -> io::Result<()>
+    where
+        E: Elem + Locked,
+        I: IntoIterator,
+        I::Item: Elem + Locked,
+        W: AsyncWrite + Unpin,
+    {
+        let size = self.options.chunk_size.max(1);
+        for chunk in self.chunks(root, children) {
+            for piece in chunk?.as_bytes().chunks(size) {
+                write_all(&mut writer, piece).await?;
+            }
+        }
+        poll_fn(|cx| Pin::new(&mut writer).poll_flush(cx)).await
+    }
+
+    ///
+    /// Render elements as an iterator of string chunks of about [`RenderOptions::chunk_size`] bytes.
+    /// The head of `root` comes first, then each of the `children`, then the tail of `root`.
+    ///
+    pub fn chunks<E, I>(&mut self, root: E, children: I) -> Chunks<'_, E, I::IntoIter>
+    where
+        E: Elem + Locked,
+        I: IntoIterator,
+        I::Item: Elem + Locked,
+    {
+        Chunks {
+            steps: Steps::new(self),
+            root: Some(root),
+            tail: None,
+            children: Some(children.into_iter()),
+            buf: String::new(),
+            done: false,
+        }
+    }
+
+    fn run(
+        &mut self,
+        writer: &mut dyn fmt::Write,
+        func: impl FnOnce(ElemWrite) -> fmt::Result,
+    ) -> Result<(), Error> {
+        let mut steps = Steps::new(self);
+        steps.step(writer, func)?;
+        steps.finish(writer)
+    }
+}
+
+///
+/// A minimal async byte writer that [`Renderer::render_async`] writes to.
+///
+/// It has the same shape as the `AsyncWrite` traits found in the async ecosystem,
+/// so implementing it for a wrapper around one of those is a matter of forwarding.
+///
+pub trait AsyncWrite {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>>;
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<io::Result<()>>;
+}
+
+impl AsyncWrite for Vec<u8> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _: &mut task::Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+    fn poll_flush(self: Pin<&mut Self>, _: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin + ?Sized> AsyncWrite for &mut W {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut **self.get_mut()).poll_write(cx, buf)
+    }
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut **self.get_mut()).poll_flush(cx)
+    }
+}
+
+async fn write_all<W: AsyncWrite + Unpin>(writer: &mut W, mut bytes: &[u8]) -> io::Result<()> {
+    while !bytes.is_empty() {
+        let n = poll_fn(|cx| Pin::new(&mut *writer).poll_write(cx, bytes)).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+        bytes = &bytes[n..];
+    }
+    Ok(())
+}
+
+///
+/// Iterator of rendered chunks. See [`Renderer::chunks`].
+///
+#[must_use]
+pub struct Chunks<'a, E: Elem, I> {
+    steps: Steps<'a>,
+    root: Option<E>,
+    tail: Option<E::Tail>,
+    children: Option<I>,
+    buf: String,
+    done: bool,
+}
+
+impl<E: Elem, I: Iterator> Iterator for Chunks<'_, E, I>
+where
+    I::Item: Elem,
+{
+    type Item = Result<String, Error>;
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let buf = &mut self.buf;
+            let res = if let Some(root) = self.root.take() {
+                let tail = &mut self.tail;
+                self.steps.step(buf, |mut e| {
+                    *tail = Some(root.render_head(e.borrow_mut2())?);
+                    Ok(())
+                })
+            } else if let Some(child) = self.children.as_mut().and_then(|c| c.next()) {
+                self.steps.step(buf, |mut e| e.render_inner(child))
+            } else {
+                self.children = None;
+                self.done = true;
+                match self.tail.take() {
+                    Some(tail) => self.steps.step(buf, |e| tail.render(e)),
+                    None => Ok(()),
+                }
+                .and_then(|_| self.steps.finish(buf))
+            };
+
+            if let Err(e) = res {
+                self.done = true;
+                self.buf.clear();
+                return Some(Err(e));
+            }
+            if !self.buf.is_empty()
+                && self.buf.len() >= self.steps.options.chunk_size
+                && !self.steps.sink.in_tag()
+            {
+                return Some(Ok(std::mem::take(&mut self.buf)));
+            }
+        }
+        Some(std::mem::take(&mut self.buf))
+            .filter(|buf| !buf.is_empty())
+            .map(Ok)
+    }
+}
+
+///
+/// The markup language that a [`Renderer`] serializes for.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Mode {
+    ///
+    /// XML, such as SVG. Elements without an end tag, and elements that turn out
+    /// to have no content, are written as `<x/>`.
+    ///
+    Xml,
+    ///
+    /// XHTML, written so it also works as HTML. Void elements are written as `<br />`
+    /// and other elements always get an end tag.
+    ///
+    Xhtml,
+    ///
+    /// HTML5. Void elements like `<br>` have no end tag and cannot have children,
+    /// other elements always get an end tag and boolean attributes are written as just their name.
+    ///
+    Html,
+}
+
+///
+/// What to do when an element is given the same attribute more than once.
+/// In [`Mode::Html`] names that differ only in ASCII case are the same attribute.
+///
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DuplicateAttrs {
+    ///
+    /// Write every attribute as it is given, duplicates included.
+    ///
+    #[default]
+    Allow,
+    ///
+    /// Fail the render. Nothing of the tag is written.
+    ///
+    Error,
+    ///
+    /// Keep the attribute where it was first written, with the last value.
+    ///
+    LastWins,
+    ///
+    /// Join the values of `class` with a space, leaving out classes already there,
+    /// and the declarations of `style` with a `;`. Other attributes keep the last value.
+    ///
+    Merge,
+}
+
+///
+/// Renders a tree over several calls, keeping the state in between.
+///
+struct Steps<'a> {
+    fmt: FmtGuard<'a>,
+    options: &'a RenderOptions,
+    ctx: Context,
+    sink: TextState,
+    pending: usize,
+    written: bool,
+}
+
+impl<'a> Steps<'a> {
+    fn new<F: Fmt>(renderer: &'a mut Renderer<F>) -> Self {
+        Steps {
+            fmt: FmtGuard::new(&mut renderer.fmt),
+            options: &renderer.options,
+            ctx: Context::new(&renderer.options),
+            sink: TextState::default(),
+            pending: 0,
+            written: false,
+        }
+    }
+
+    fn step(
+        &mut self,
+        writer: &mut dyn fmt::Write,
+        func: impl FnOnce(ElemWrite) -> fmt::Result,
+    ) -> Result<(), Error> {
+        self.with_text_sink(writer, |sink, fmt, ctx| {
+            func(ElemWrite(sink, fmt, ctx)).map_err(|e| ctx.take_error(e))
+        })
+    }
+
+    fn step_sink(
+        &mut self,
+        sink: &mut dyn Sink,
+        func: impl FnOnce(ElemWrite) -> fmt::Result,
+    ) -> Result<(), Error> {
+        func(ElemWrite(sink, &mut self.fmt, &mut self.ctx)).map_err(|e| self.ctx.take_error(e))
+    }
+
+    ///
+    /// Serialize to the writer, picking up where the last step left off.
+    ///
+    fn with_text_sink(
+        &mut self,
+        writer: &mut dyn fmt::Write,
+        func: impl FnOnce(
+            &mut TextSink<&mut dyn fmt::Write>,
+            &mut dyn Fmt,
+            &mut Context,
+        ) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let mut trailing;
+        let writer: &mut dyn fmt::Write = match self.options.trailing_newline {
+            None => writer,
+            Some(_) => {
+                trailing = TrailingNewline {
+                    writer,
+                    newline: &self.options.newline,
+                    pending: &mut self.pending,
+                    written: &mut self.written,
+                };
+                &mut trailing
+            }
+        };
+        let state = std::mem::take(&mut self.sink);
+        let mut sink = TextSink::resume(writer, self.options.text, state);
+        let res = func(&mut sink, &mut self.fmt, &mut self.ctx);
+        self.sink = sink.suspend();
+        res
+    }
+
+    fn finish(&mut self, writer: &mut dyn fmt::Write) -> Result<(), Error> {
+        self.fmt.reset();
+        self.with_text_sink(writer, |sink, _, _| sink.flush())?;
+        if self.options.trailing_newline == Some(true) && self.written {
+            writer.write_str(&self.options.newline)?;
+        }
+        Ok(())
+    }
+}
+
+///
+/// Options for building a [`Renderer`] that uses [`PrettyFmt`].
+///
+#[derive(Clone, Debug)]
+#[must_use]
+pub struct RenderOptions {
+    indent: Cow<'static, str>,
+    newline: Cow<'static, str>,
+    depth: usize,
+    inline: bool,
+    trailing_newline: Option<bool>,
+    chunk_size: usize,
+    pub(crate) text: TextOptions,
+    pub(crate) duplicate_attrs: DuplicateAttrs,
+    pub(crate) namespaces: Vec<Namespace>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RenderOptions {
+    pub fn new() -> Self {
+        RenderOptions {
+            indent: Cow::Borrowed("\t"),
+            newline: Cow::Borrowed("\n"),
+            depth: 0,
+            inline: false,
+            trailing_newline: None,
+            chunk_size: 8 * 1024,
+            text: TextOptions::default(),
+            duplicate_attrs: DuplicateAttrs::default(),
+            namespaces: Vec::new(),
+        }
+    }
+
+    /// The string written out once per level of indentation. Defaults to a tab.
+    pub fn indent(mut self, indent: impl Into<Cow<'static, str>>) -> Self {
+        self.indent = indent.into();
+        self
+    }
+
+    /// Indent with the given number of spaces per level.
+    pub fn indent_width(self, width: usize) -> Self {
+        self.indent(" ".repeat(width))
+    }
+
+    /// The string written out at the end of each line. Defaults to `\n`.
+    pub fn newline(mut self, newline: impl Into<Cow<'static, str>>) -> Self {
+        self.newline = newline.into();
+        self
+    }
+
+    /// The indentation depth of the root elements. Defaults to zero.
+    pub fn depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Start off in inline mode, as if the root was wrapped in `Elem::inline()`.
+    pub fn inline(mut self, inline: bool) -> Self {
+        self.inline = inline;
+        self
+    }
+
+    ///
+    /// If true, make sure non-empty output ends with exactly one newline.
+    /// If false, make sure it does not end with one.
+    /// By default the output is left as the elements wrote it.
+    ///
+    pub fn trailing_newline(mut self, val: bool) -> Self {
+        self.trailing_newline = Some(val);
+        self
+    }
+
+    ///
+    /// How many bytes [`Renderer::chunks`] collects before handing a chunk on.
+    /// A chunk only ends between elements and never inside a tag, so chunks can
+    /// be larger. Zero makes every element its own chunk. Defaults to 8 KiB.
+    ///
+    pub fn chunk_size(mut self, bytes: usize) -> Self {
+        self.chunk_size = bytes;
+        self
+    }
+
+    ///
+    /// Serialize for the given markup language.
+    /// By default tags are written out exactly as the elements describe them.
+    ///
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.text.mode = Some(mode);
+        self
+    }
+
+    ///
+    /// Which characters to escape in text and attribute values. Defaults to [`Escape::Full`].
+    ///
+    pub fn escape(mut self, escape: Escape) -> Self {
+        self.text.escape = escape;
+        self
     }
+
+    ///
+    /// What to do with characters that XML 1.0 does not allow, such as `\u{1B}`,
+    /// anywhere in the output. Defaults to [`InvalidChars::Error`].
+    ///
+    pub fn invalid_chars(mut self, invalid: InvalidChars) -> Self {
+        self.text.invalid_chars = invalid;
+        self
+    }
+
+    ///
+    /// What to do when an element is given the same attribute twice.
+    /// Defaults to [`DuplicateAttrs::Allow`].
+    ///
+    pub fn duplicate_attrs(mut self, policy: DuplicateAttrs) -> Self {
+        self.duplicate_attrs = policy;
+        self
+    }
+
+    ///
+    /// Treat the namespace as declared already, for rendering a fragment
+    /// that goes inside an element that declares it.
+    ///
+    pub fn namespace(mut self, ns: Namespace) -> Self {
+        self.namespaces.push(ns);
+        self
+    }
+
+    ///
+    /// Check that tag and attribute names are valid, following the `Name` production
+    /// of XML, or the HTML rules in [`Mode::Html`]. Defaults to true.
+    ///
+    pub fn validate_names(mut self, validate: bool) -> Self {
+        self.text.validate_names = validate;
+        self
+    }
+
+    pub fn build(self) -> Renderer {
+        Renderer::with_options(self)
+    }
+}
+
+///
+/// Formatter adaptor that remembers what the elements changed, so a render
+/// that stops part way can put the formatter back the way it found it.
+///
+struct FmtGuard<'a> {
+    fmt: &'a mut dyn Fmt,
+    depth: isize,
+    inline: Option<bool>,
+    tab: Option<Cow<'static, str>>,
 }
 
-// pub trait Fmt {
-//     fn push(&mut self);
-//     fn pop(&mut self);
-//     fn tabs(&mut self, w: &mut dyn fmt::Write) -> fmt::Result;
-//     fn end_tag(&mut self, w: &mut dyn fmt::Write) -> fmt::Result;
-//     fn set_inline_mode(&mut self, val: bool);
-//     fn is_inline_mode(&mut self) -> bool;
-// }
+impl<'a> FmtGuard<'a> {
+    fn new(fmt: &'a mut dyn Fmt) -> Self {
+        FmtGuard {
+            fmt,
+            depth: 0,
+            inline: None,
+            tab: None,
+        }
+    }
 
+    fn reset(&mut self) {
+        for _ in 0..self.depth {
+            self.fmt.pop();
+        }
+        for _ in self.depth..0 {
+            self.fmt.push();
+        }
+        self.depth = 0;
+        if let Some(inline) = self.inline.take() {
+            self.fmt.set_inline_mode(inline);
+        }
+        if let Some(tab) = self.tab.take() {
+            self.fmt.swap_tab(tab);
+        }
+    }
+}
+
+impl Drop for FmtGuard<'_> {
+    fn drop(&mut self) {
+        self.reset();
+    }
+}
+
+impl Fmt for FmtGuard<'_> {
+    fn push(&mut self) {
+        self.depth += 1;
+        self.fmt.push()
+    }
+    fn pop(&mut self) {
+        self.depth -= 1;
+        self.fmt.pop()
+    }
+    fn tabs(&mut self, w: &mut dyn fmt::Write) -> fmt::Result {
+        self.fmt.tabs(w)
+    }
+    fn end_tag(&mut self, w: &mut dyn fmt::Write) -> fmt::Result {
+        self.fmt.end_tag(w)
+    }
+    fn set_inline_mode(&mut self, val: bool) {
+        if self.inline.is_none() {
+            self.inline = Some(self.fmt.is_inline_mode());
+        }
+        self.fmt.set_inline_mode(val)
+    }
+    fn is_inline_mode(&mut self) -> bool {
+        self.fmt.is_inline_mode()
+    }
+    fn swap_tab(&mut self, tab: Cow<'static, str>) -> Cow<'static, str> {
+        let old = self.fmt.swap_tab(tab);
+        if self.tab.is_none() {
+            self.tab = Some(old.clone());
+        }
+        old
+    }
+}
+
+///
+/// Writer adaptor that holds back trailing newlines until more is written.
+///
+struct TrailingNewline<'a> {
+    writer: &'a mut dyn fmt::Write,
+    newline: &'a str,
+    pending: &'a mut usize,
+    written: &'a mut bool,
+}
+
+impl fmt::Write for TrailingNewline<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if s.is_empty() {
+            return Ok(());
+        }
+        *self.written = true;
+
+        let mut rest = s;
+        let mut newlines = 0;
+        if !self.newline.is_empty() {
+            while let Some(r) = rest.strip_suffix(self.newline) {
+                rest = r;
+                newlines += 1;
+            }
+        }
+        if rest.is_empty() {
+            *self.pending += newlines;
+            return Ok(());
+        }
+        for _ in 0..std::mem::replace(self.pending, newlines) {
+            self.writer.write_str(self.newline)?;
+        }
+        self.writer.write_str(rest)
+    }
+}
+
+///
+/// Controls the whitespace that is inserted between elements.
+///
+/// `ElemWrite` calls into this as it renders.
+/// `push()` and `pop()` are called when entering and leaving an element's children.
+/// `tabs()` is called before a tag is written and `end_tag()` after.
+///
+pub trait Fmt {
+    fn push(&mut self);
+    fn pop(&mut self);
+    fn tabs(&mut self, w: &mut dyn fmt::Write) -> fmt::Result;
+    fn end_tag(&mut self, w: &mut dyn fmt::Write) -> fmt::Result;
+    fn set_inline_mode(&mut self, val: bool);
+    fn is_inline_mode(&mut self) -> bool;
+
+    ///
+    /// Replace the string used for one level of indentation, returning the old one.
+    /// Used by `Elem::with_tab()`. Formatters that do not indent can ignore this.
+    ///
+    fn swap_tab(&mut self, tab: Cow<'static, str>) -> Cow<'static, str> {
+        tab
+    }
+}
+
+///
+/// Formatter that puts every tag on its own line, indented by nesting depth.
+///
 pub struct PrettyFmt {
     tabs: usize,
-    pub tab_char: &'static str,
+    indent: Cow<'static, str>,
+    pub newline: Cow<'static, str>,
     inline: bool,
 }
 
@@ -66,60 +717,86 @@ impl Default for PrettyFmt {
 
 impl PrettyFmt {
     pub fn new() -> Self {
+        PrettyFmt::with_options(&RenderOptions::new())
+    }
+
+    pub fn with_options(options: &RenderOptions) -> Self {
         PrettyFmt {
-            tabs: 0,
-            tab_char: "\t",
-            inline: false,
+            tabs: options.depth,
+            indent: options.indent.clone(),
+            newline: options.newline.clone(),
+            inline: options.inline,
         }
     }
+
+    ///
+    /// The string written for one level of indentation.
+    ///
+    pub fn indent(&self) -> &str {
+        &self.indent
+    }
+
+    ///
+    /// Set the string written for one level of indentation.
+    ///
+    pub fn set_indent(&mut self, indent: impl Into<Cow<'static, str>>) {
+        self.indent = indent.into();
+    }
 }
-impl PrettyFmt {
-    pub fn set_inline_mode(&mut self, val: bool) {
+impl Fmt for PrettyFmt {
+    fn set_inline_mode(&mut self, val: bool) {
         self.inline = val;
     }
-    pub fn is_inline_mode(&mut self) -> bool {
+    fn is_inline_mode(&mut self) -> bool {
         self.inline
     }
-    pub fn tabs(&mut self, w: &mut dyn fmt::Write) -> fmt::Result {
+    fn tabs(&mut self, w: &mut dyn fmt::Write) -> fmt::Result {
         if !self.inline {
             for _ in 0..self.tabs {
-                write!(w, "{}", self.tab_char)?;
+                w.write_str(&self.indent)?;
             }
         }
 
         Ok(())
     }
-    pub fn push(&mut self) {
+    fn push(&mut self) {
         //if !self.inline {
         self.tabs += 1;
         //}
     }
-    pub fn pop(&mut self) {
+    fn pop(&mut self) {
         //if !self.inline {
         self.tabs -= 1;
         //}
     }
-    pub fn end_tag(&mut self, w: &mut dyn fmt::Write) -> fmt::Result {
+    fn end_tag(&mut self, w: &mut dyn fmt::Write) -> fmt::Result {
         if !self.inline {
-            writeln!(w)?;
+            w.write_str(&self.newline)?;
         }
         Ok(())
     }
+    fn swap_tab(&mut self, tab: Cow<'static, str>) -> Cow<'static, str> {
+        std::mem::replace(&mut self.indent, tab)
+    }
 }
 
-// pub struct NoFmt;
-// impl Fmt for NoFmt {
-//     fn tabs(&mut self, _: &mut dyn fmt::Write) -> fmt::Result {
-//         Ok(())
-//     }
-//     fn push(&mut self) {}
-//     fn pop(&mut self) {}
-//     fn end_tag(&mut self, _: &mut dyn fmt::Write) -> fmt::Result {
-//         Ok(())
-//     }
-//     fn set_inline_mode(&mut self, _: bool) {}
+///
+/// Formatter that inserts no whitespace at all.
+///
+#[derive(Copy, Clone, Default)]
+pub struct NoFmt;
+impl Fmt for NoFmt {
+    fn tabs(&mut self, _: &mut dyn fmt::Write) -> fmt::Result {
+        Ok(())
+    }
+    fn push(&mut self) {}
+    fn pop(&mut self) {}
+    fn end_tag(&mut self, _: &mut dyn fmt::Write) -> fmt::Result {
+        Ok(())
+    }
+    fn set_inline_mode(&mut self, _: bool) {}
 
-//     fn is_inline_mode(&mut self) -> bool {
-//         true
-//     }
-// }
+    fn is_inline_mode(&mut self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,380 @@
+//!
+//! CSS selectors for finding nodes in a [`Document`](crate::dom::Document)
+//!
+
+use super::*;
+use crate::dom::{Document, NodeId};
+
+///
+/// A parsed CSS selector: type, `*`, `.class`, `#id` and attribute selectors,
+/// the descendant and `>` combinators, and `,` separated lists.
+/// Characters in names can be escaped with `\`, as in `svg\:rect`.
+///
+#[derive(Clone, Debug)]
+pub struct Selector {
+    list: Vec<Complex>,
+}
+
+#[derive(Clone, Debug)]
+struct Complex {
+    // The combinator of the first compound is unused.
+    parts: Vec<(Combinator, Compound)>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Combinator {
+    Descendant,
+    Child,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Compound {
+    name: Option<String>,
+    conds: Vec<Cond>,
+}
+
+#[derive(Clone, Debug)]
+enum Cond {
+    Class(String),
+    Id(String),
+    Attr(String, Option<(Op, String)>),
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Op {
+    Equals,
+    Includes,
+    Prefix,
+    Suffix,
+    Contains,
+}
+
+///
+/// Error returned when a selector cannot be parsed.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SelectorError {
+    selector: String,
+    position: usize,
+}
+
+impl SelectorError {
+    pub fn selector(&self) -> &str {
+        &self.selector
+    }
+
+    ///
+    /// The byte offset in the selector where parsing failed.
+    ///
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl fmt::Display for SelectorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid selector {:?} at position {}",
+            self.selector, self.position
+        )
+    }
+}
+
+impl std::error::Error for SelectorError {}
+
+impl std::str::FromStr for Selector {
+    type Err = SelectorError;
+    fn from_str(s: &str) -> Result<Self, SelectorError> {
+        Selector::parse(s)
+    }
+}
+
+impl Selector {
+    pub fn parse(s: &str) -> Result<Self, SelectorError> {
+        let mut p = Parser { s, pos: 0 };
+        let mut list = vec![p.complex()?];
+        while p.eat(',') {
+            list.push(p.complex()?);
+        }
+        p.skip_ws();
+        if p.pos != s.len() {
+            return Err(p.error());
+        }
+        Ok(Selector { list })
+    }
+
+    ///
+    /// Check if the node is an element matched by this selector.
+    ///
+    pub fn matches(&self, doc: &Document, id: NodeId) -> bool {
+        self.list
+            .iter()
+            .any(|c| c.matches(doc, id, c.parts.len() - 1))
+    }
+}
+
+impl Complex {
+    fn matches(&self, doc: &Document, id: NodeId, index: usize) -> bool {
+        let (combinator, compound) = &self.parts[index];
+        if !compound.matches(doc, id) {
+            return false;
+        }
+        if index == 0 {
+            return true;
+        }
+        match combinator {
+            Combinator::Child => doc
+                .parent(id)
+                .is_some_and(|p| self.matches(doc, p, index - 1)),
+            Combinator::Descendant => doc
+                .ancestors(id)
+                .skip(1)
+                .any(|a| self.matches(doc, a, index - 1)),
+        }
+    }
+}
+
+impl Compound {
+    fn matches(&self, doc: &Document, id: NodeId) -> bool {
+        let Some(name) = doc.name(id) else {
+            return false;
+        };
+        if self.name.as_ref().is_some_and(|n| n != name) {
+            return false;
+        }
+        self.conds.iter().all(|c| match c {
+            Cond::Class(class) => doc
+                .attr(id, "class")
+                .is_some_and(|v| v.split_whitespace().any(|c| c == class)),
+            Cond::Id(i) => doc.attr(id, "id") == Some(i.as_str()),
+            Cond::Attr(name, test) => match (doc.attr(id, name), test) {
+                (None, _) => false,
+                (Some(_), None) => true,
+                (Some(v), Some((op, want))) => match op {
+                    Op::Equals => v == want,
+                    Op::Includes => v.split_whitespace().any(|w| w == want),
+                    Op::Prefix => !want.is_empty() && v.starts_with(want.as_str()),
+                    Op::Suffix => !want.is_empty() && v.ends_with(want.as_str()),
+                    Op::Contains => !want.is_empty() && v.contains(want.as_str()),
+                },
+            },
+        })
+    }
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self) -> SelectorError {
+        SelectorError {
+            selector: self.s.to_string(),
+            position: self.pos,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.s[self.pos..].chars().next()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_ws();
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_ws(&mut self) -> bool {
+        let start = self.pos;
+        while let Some(c) = self.peek().filter(|c| c.is_whitespace()) {
+            self.pos += c.len_utf8();
+        }
+        self.pos != start
+    }
+
+    fn ident(&mut self) -> Result<String, SelectorError> {
+        self.name(false)
+    }
+
+    ///
+    /// An identifier, with `\` escapes resolved. With `prefixed`, it may also
+    /// contain a `:`, as in namespaced attribute names.
+    ///
+    fn name(&mut self, prefixed: bool) -> Result<String, SelectorError> {
+        let mut out = String::new();
+        while let Some(c) = self.peek() {
+            if c == '\\' {
+                self.pos += 1;
+                out.push(self.escape()?);
+            } else if c.is_alphanumeric() || c == '-' || c == '_' || (prefixed && c == ':') {
+                self.pos += c.len_utf8();
+                out.push(c);
+            } else {
+                break;
+            }
+        }
+        if out.is_empty() {
+            return Err(self.error());
+        }
+        Ok(out)
+    }
+
+    ///
+    /// The char after a `\`, either as is or given as up to six hex digits.
+    ///
+    fn escape(&mut self) -> Result<char, SelectorError> {
+        let rest = &self.s[self.pos..];
+        let hex = rest
+            .char_indices()
+            .take_while(|&(i, c)| i < 6 && c.is_ascii_hexdigit())
+            .count();
+        if hex == 0 {
+            let c = self
+                .peek()
+                .filter(|&c| c != '\n')
+                .ok_or_else(|| self.error())?;
+            self.pos += c.len_utf8();
+            return Ok(c);
+        }
+        let c = u32::from_str_radix(&rest[..hex], 16)
+            .ok()
+            .and_then(char::from_u32)
+            .filter(|&c| c != '\0')
+            .unwrap_or('\u{FFFD}');
+        self.pos += hex;
+        // A single whitespace ends the escape.
+        if let Some(ws) = self.peek().filter(|c| c.is_whitespace()) {
+            self.pos += ws.len_utf8();
+        }
+        Ok(c)
+    }
+
+    fn complex(&mut self) -> Result<Complex, SelectorError> {
+        self.skip_ws();
+        let mut parts = vec![(Combinator::Descendant, self.compound()?)];
+        loop {
+            let ws = self.skip_ws();
+            let combinator = match self.peek() {
+                Some('>') => {
+                    self.pos += 1;
+                    self.skip_ws();
+                    Combinator::Child
+                }
+                Some(',') | None => break,
+                _ if ws => Combinator::Descendant,
+                _ => return Err(self.error()),
+            };
+            parts.push((combinator, self.compound()?));
+        }
+        Ok(Complex { parts })
+    }
+
+    fn compound(&mut self) -> Result<Compound, SelectorError> {
+        let mut compound = Compound::default();
+        match self.peek() {
+            Some('*') => self.pos += 1,
+            Some(c) if c.is_alphanumeric() || c == '-' || c == '_' || c == '\\' => {
+                compound.name = Some(self.ident()?)
+            }
+            Some('.' | '#' | '[') => {}
+            _ => return Err(self.error()),
+        }
+        loop {
+            match self.peek() {
+                Some('.') => {
+                    self.pos += 1;
+                    compound.conds.push(Cond::Class(self.ident()?));
+                }
+                Some('#') => {
+                    self.pos += 1;
+                    compound.conds.push(Cond::Id(self.ident()?));
+                }
+                Some('[') => {
+                    self.pos += 1;
+                    compound.conds.push(self.attr()?);
+                }
+                _ => return Ok(compound),
+            }
+        }
+    }
+
+    fn attr(&mut self) -> Result<Cond, SelectorError> {
+        self.skip_ws();
+        let name = self.name(true)?;
+        if self.eat(']') {
+            return Ok(Cond::Attr(name, None));
+        }
+        let op = match self.peek() {
+            Some('=') => Op::Equals,
+            Some(c) => {
+                let op = match c {
+                    '~' => Op::Includes,
+                    '^' => Op::Prefix,
+                    '$' => Op::Suffix,
+                    '*' => Op::Contains,
+                    _ => return Err(self.error()),
+                };
+                self.pos += 1;
+                if self.peek() != Some('=') {
+                    return Err(self.error());
+                }
+                op
+            }
+            None => return Err(self.error()),
+        };
+        self.pos += 1;
+        self.skip_ws();
+        let value = match self.peek() {
+            Some(q @ ('"' | '\'')) => {
+                self.pos += 1;
+                let len = self.s[self.pos..].find(q).ok_or_else(|| self.error())?;
+                let value = self.s[self.pos..self.pos + len].to_string();
+                self.pos += len + 1;
+                value
+            }
+            _ => self.ident()?,
+        };
+        if !self.eat(']') {
+            return Err(self.error());
+        }
+        Ok(Cond::Attr(name, Some((op, value))))
+    }
+}
+
+impl Document {
+    ///
+    /// All nodes below `id`, in document order.
+    ///
+    pub fn descendants(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        let mut stack: Vec<NodeId> = self.children(id).iter().rev().copied().collect();
+        std::iter::from_fn(move || {
+            let id = stack.pop()?;
+            stack.extend(self.children(id).iter().rev());
+            Some(id)
+        })
+    }
+
+    ///
+    /// Find all elements in the document that match a CSS selector, in document order.
+    ///
+    pub fn select(&self, selector: &str) -> Result<Vec<NodeId>, SelectorError> {
+        self.select_in(self.root(), selector)
+    }
+
+    ///
+    /// Like [`Document::select`], but only considers nodes below `id`.
+    ///
+    pub fn select_in(&self, id: NodeId, selector: &str) -> Result<Vec<NodeId>, SelectorError> {
+        let selector = Selector::parse(selector)?;
+        Ok(self
+            .descendants(id)
+            .filter(|&n| selector.matches(self, n))
+            .collect())
+    }
+}
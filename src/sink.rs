@@ -0,0 +1,678 @@
+//!
+//! Sink trait that elements emit markup events into
+//!
+
+use super::*;
+use crate::context::Context;
+use crate::render::Mode;
+use crate::tools::{Escape, EscapeGuard, InvalidChars};
+use fmt::Write;
+
+///
+/// Receives markup as a sequence of events, which [`TextSink`] serializes.
+/// A start tag is `start_tag()`, an `attribute()` for each attribute, then
+/// `start_tag_end()` if children and an `end_tag()` follow, or else `empty_tag_end()`.
+///
+pub trait Sink {
+    /// The start of a start tag.
+    fn start_tag(&mut self, name: &str) -> Result<(), Error>;
+
+    /// An attribute of the current start tag.
+    fn attribute(&mut self, name: &str, value: &str) -> Result<(), Error>;
+
+    ///
+    /// A boolean attribute of the current start tag.
+    /// By default it is reported as an attribute whose value is its name.
+    ///
+    fn flag(&mut self, name: &str) -> Result<(), Error> {
+        self.attribute(name, name)
+    }
+
+    /// The end of a start tag whose children and end tag follow.
+    fn start_tag_end(&mut self) -> Result<(), Error>;
+
+    ///
+    /// The end of a start tag that has no end tag.
+    /// `ending` is what the element wants written before the closing `>`, usually `/`.
+    ///
+    fn empty_tag_end(&mut self, ending: &str) -> Result<(), Error>;
+
+    /// An end tag.
+    fn end_tag(&mut self, name: &str) -> Result<(), Error>;
+
+    /// Text content that needs escaping, possibly over several calls.
+    fn text(&mut self, text: &str) -> Result<(), Error>;
+
+    /// Markup that is passed through as is.
+    fn raw(&mut self, markup: &str) -> Result<(), Error>;
+
+    /// A comment.
+    fn comment(&mut self, text: &str) -> Result<(), Error>;
+
+    ///
+    /// The content of a `script` or `style` element, which HTML does not decode.
+    /// By default it is reported as text.
+    ///
+    fn raw_text(&mut self, text: &str) -> Result<(), Error> {
+        self.text(text)
+    }
+
+    ///
+    /// Text to be written as a CDATA section. By default it is reported as text.
+    ///
+    fn cdata(&mut self, text: &str) -> Result<(), Error> {
+        self.text(text)
+    }
+
+    ///
+    /// A document type declaration, such as `html` for `<!DOCTYPE html>`.
+    /// By default it is reported as raw markup.
+    ///
+    fn doctype(&mut self, doctype: &str) -> Result<(), Error> {
+        self.raw(&format!("<!DOCTYPE {}>", doctype))
+    }
+
+    ///
+    /// A processing instruction, including the XML declaration, whose target is `xml`.
+    /// By default it is reported as raw markup.
+    ///
+    fn processing_instruction(&mut self, target: &str, data: &str) -> Result<(), Error> {
+        if data.is_empty() {
+            self.raw(&format!("<?{}?>", target))
+        } else {
+            self.raw(&format!("<?{} {}?>", target, data))
+        }
+    }
+
+    ///
+    /// Whitespace inserted by the [`Fmt`](crate::render::Fmt) to lay out the markup.
+    /// It is not part of the content, so by default it is ignored.
+    ///
+    fn whitespace(&mut self, whitespace: &str) -> Result<(), Error> {
+        let _ = whitespace;
+        Ok(())
+    }
+}
+
+///
+/// Elements that never have children or an end tag in HTML.
+///
+const HTML_VOID: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+pub(crate) fn is_html_void(name: &str) -> bool {
+    HTML_VOID.iter().any(|v| v.eq_ignore_ascii_case(name))
+}
+
+///
+/// Elements whose content is not markup in HTML.
+///
+const HTML_RAW_TEXT: &[&str] = &["script", "style"];
+
+pub(crate) fn is_html_raw_text(name: &str) -> bool {
+    HTML_RAW_TEXT.iter().any(|v| v.eq_ignore_ascii_case(name))
+}
+
+///
+/// Where the end tag of the raw text element `name` starts: `</` and the name in any case,
+/// followed by whitespace, `/`, `>` or the end of the text.
+///
+pub(crate) fn find_raw_text_end(text: &str, name: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let mut from = 0;
+    while let Some(i) = text[from..].find("</") {
+        let start = from + i;
+        let end = start + 2 + name.len();
+        let matches = bytes
+            .get(start + 2..end)
+            .is_some_and(|n| n.eq_ignore_ascii_case(name.as_bytes()));
+        let ends = bytes
+            .get(end)
+            .is_none_or(|&b| b.is_ascii_whitespace() || b == b'/' || b == b'>');
+        if matches && ends {
+            return Some(start);
+        }
+        from = start + 2;
+    }
+    None
+}
+
+///
+/// An HTML tag name starts with an ascii letter and runs until whitespace, `/` or `>`.
+///
+fn is_html_tag_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name
+            .chars()
+            .all(|c| tools::is_xml_char(c) && !c.is_whitespace() && !"/><&\"'=".contains(c))
+}
+
+///
+/// An HTML attribute name can be anything but whitespace, controls, quotes, `/`, `>` and `=`.
+///
+fn is_html_attr_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().all(|c| {
+            tools::is_xml_char(c)
+                && !c.is_control()
+                && !c.is_whitespace()
+                && !"/><&\"'=".contains(c)
+        })
+}
+
+///
+/// Attributes whose value is a URL.
+///
+const URL_ATTRS: &[&str] = &[
+    "href",
+    "src",
+    "action",
+    "formaction",
+    "cite",
+    "poster",
+    "background",
+    "xlink:href",
+];
+
+pub(crate) fn is_url_attr(name: &str) -> bool {
+    URL_ATTRS.iter().any(|v| v.eq_ignore_ascii_case(name))
+}
+
+///
+/// Reject URLs that run script, and percent-encode characters that cannot appear in a URL.
+///
+pub(crate) fn url_value(name: &str, value: &str) -> Result<String, Error> {
+    // Browsers ignore leading spaces and controls and any tabs or newlines when reading the scheme.
+    let scheme: String = value
+        .trim_start_matches(|c: char| c <= ' ')
+        .chars()
+        .filter(|c| !matches!(c, '\t' | '\n' | '\r'))
+        .take_while(|&c| c != ':')
+        .collect();
+    if value.contains(':')
+        && ["javascript", "vbscript"]
+            .iter()
+            .any(|s| scheme.eq_ignore_ascii_case(s))
+    {
+        return Err(Error::validation(format!(
+            "attribute {} has a {} URL",
+            name, scheme
+        )));
+    }
+
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c.is_ascii_control() || matches!(c, ' ' | '"' | '<' | '>' | '`') {
+            write!(out, "%{:02X}", c as u32)?;
+        } else {
+            out.push(c);
+        }
+    }
+    Ok(out)
+}
+
+///
+/// Sink that serializes the events as markup to a writer.
+///
+/// Some modes need to look ahead before deciding what to write,
+/// so call [`TextSink::finish`] once all events are in.
+///
+/// The content of `script` and `style` is written as is in [`Mode::Html`],
+/// with only `<` and `&` escaped in the XML modes, and like any other text without a mode.
+///
+pub struct TextSink<W> {
+    writer: W,
+    options: TextOptions,
+    state: TextState,
+}
+
+///
+/// How a [`TextSink`] serializes. Set through [`RenderOptions`](crate::render::RenderOptions).
+///
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct TextOptions {
+    pub mode: Option<Mode>,
+    pub escape: Escape,
+    pub invalid_chars: InvalidChars,
+    pub validate_names: bool,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        TextOptions {
+            mode: None,
+            escape: Escape::default(),
+            invalid_chars: InvalidChars::default(),
+            validate_names: true,
+        }
+    }
+}
+
+///
+/// What a [`TextSink`] holds on to between events.
+///
+#[derive(Default)]
+pub(crate) struct TextState {
+    tag: String,
+    pending: Pending,
+    whitespace: String,
+    /// How many `]` the text so far ends with, for [`Escape::Minimal`].
+    brackets: usize,
+}
+
+impl TextState {
+    ///
+    /// Whether the output so far ends part way through a start tag.
+    ///
+    pub(crate) fn in_tag(&self) -> bool {
+        self.pending == Pending::Open
+    }
+}
+
+///
+/// A start tag that was seen but whose end tag has not been decided on yet.
+/// Whitespace is held back until it is.
+///
+#[derive(Copy, Clone, Default, PartialEq, Eq)]
+enum Pending {
+    #[default]
+    None,
+    /// A void start tag was closed. Only its end tag may follow.
+    Void,
+    /// The closing `>` of a start tag was not written yet.
+    Open,
+}
+
+impl<W: fmt::Write> TextSink<W> {
+    pub fn new(writer: W) -> Self {
+        TextSink {
+            writer,
+            options: TextOptions::default(),
+            state: TextState::default(),
+        }
+    }
+
+    ///
+    /// Serialize for the given markup language instead of writing the events as they come.
+    ///
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.options.mode = Some(mode);
+        self
+    }
+
+    ///
+    /// What to do with characters not allowed in XML 1.0. Defaults to [`InvalidChars::Error`].
+    ///
+    pub fn invalid_chars(mut self, invalid: InvalidChars) -> Self {
+        self.options.invalid_chars = invalid;
+        self
+    }
+
+    ///
+    /// Check tag and attribute names. Defaults to true.
+    ///
+    pub fn validate_names(mut self, validate: bool) -> Self {
+        self.options.validate_names = validate;
+        self
+    }
+
+    ///
+    /// Which characters to escape. Defaults to [`Escape::Full`].
+    ///
+    pub fn escape(mut self, escape: Escape) -> Self {
+        self.options.escape = escape;
+        self
+    }
+
+    pub(crate) fn resume(writer: W, options: TextOptions, state: TextState) -> Self {
+        TextSink {
+            writer,
+            options,
+            state,
+        }
+    }
+
+    pub(crate) fn suspend(self) -> TextState {
+        self.state
+    }
+
+    ///
+    /// Write out anything held back and return the writer.
+    ///
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.flush()?;
+        Ok(self.writer)
+    }
+
+    pub(crate) fn flush(&mut self) -> Result<(), Error> {
+        if self.state.pending == Pending::Open {
+            self.writer.write_char('>')?;
+        }
+        self.state.pending = Pending::None;
+        self.writer.write_str(&self.state.whitespace)?;
+        self.state.whitespace.clear();
+        Ok(())
+    }
+
+    fn is_void(&self) -> bool {
+        matches!(self.options.mode, Some(Mode::Html | Mode::Xhtml)) && is_html_void(&self.state.tag)
+    }
+
+    ///
+    /// Called before anything that would be content of the current element.
+    ///
+    fn content(&mut self) -> Result<(), Error> {
+        if self.state.pending != Pending::None && self.is_void() {
+            return Err(Error::validation(format!(
+                "void element {} cannot have children",
+                self.state.tag
+            )));
+        }
+        self.flush()
+    }
+
+    ///
+    /// Write text that is not escaped, such as comments and raw markup, still applying the policy
+    /// for characters not allowed in XML.
+    ///
+    fn verbatim(&mut self, s: &str) -> Result<(), Error> {
+        let mut rest = s;
+        while let Some(i) = rest.find(|c| !tools::is_xml_char(c)) {
+            self.writer.write_str(&rest[..i])?;
+            let c = rest[i..].chars().next().unwrap();
+            match self.options.invalid_chars {
+                InvalidChars::Error => return Err(invalid_char(c)),
+                InvalidChars::Drop => {}
+                InvalidChars::Replace => self.writer.write_char('\u{FFFD}')?,
+                InvalidChars::Keep => self.writer.write_char(c)?,
+            }
+            rest = &rest[i + c.len_utf8()..];
+        }
+        self.writer.write_str(rest)?;
+        Ok(())
+    }
+
+    ///
+    /// Write a tag or attribute name. Only markup is escaped in names,
+    /// since a character reference is not allowed there.
+    ///
+    fn name(&mut self, s: &str) -> Result<(), Error> {
+        let policy = match self.options.escape {
+            Escape::AsciiSafe => Escape::Full,
+            policy => policy,
+        };
+        let guard = EscapeGuard::with_policy(&mut self.writer, policy)
+            .invalid_chars(self.options.invalid_chars);
+        write_guarded(guard, s)
+    }
+
+    fn escaped_text(&mut self, s: &str, policy: Escape) -> Result<(), Error> {
+        let mut guard = EscapeGuard::with_policy(&mut self.writer, policy)
+            .invalid_chars(self.options.invalid_chars)
+            .after_brackets(self.state.brackets);
+        let res = guard.write_str(s);
+        self.state.brackets = guard.brackets();
+        res.map_err(|e| match guard.rejected() {
+            Some(c) => invalid_char(c),
+            None => e.into(),
+        })
+    }
+
+    fn escaped_attr(&mut self, s: &str) -> Result<(), Error> {
+        let guard = EscapeGuard::with_policy(&mut self.writer, self.options.escape)
+            .invalid_chars(self.options.invalid_chars)
+            .attribute();
+        write_guarded(guard, s)
+    }
+
+    ///
+    /// Check a tag or attribute name, using the HTML rules in html.
+    ///
+    fn check_name(&self, name: &str, what: &str) -> Result<(), Error> {
+        if !self.options.validate_names {
+            return Ok(());
+        }
+        let valid = match self.options.mode {
+            Some(Mode::Html) if what == "element" => is_html_tag_name(name),
+            Some(Mode::Html) => is_html_attr_name(name),
+            _ => tools::is_xml_name(name),
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(Error::validation(format!(
+                "{:?} is not a valid {} name",
+                name, what
+            )))
+        }
+    }
+}
+
+impl<W: fmt::Write> Sink for TextSink<W> {
+    fn start_tag(&mut self, name: &str) -> Result<(), Error> {
+        self.content()?;
+        self.check_name(name, "element")?;
+        self.state.tag.clear();
+        self.state.tag.push_str(name);
+        self.state.brackets = 0;
+        self.writer.write_char('<')?;
+        self.name(name)?;
+        Ok(())
+    }
+    fn attribute(&mut self, name: &str, value: &str) -> Result<(), Error> {
+        self.check_name(name, "attribute")?;
+        self.writer.write_char(' ')?;
+        self.name(name)?;
+        self.writer.write_str("=\"")?;
+        self.escaped_attr(value)?;
+        self.writer.write_char('"')?;
+        Ok(())
+    }
+    fn flag(&mut self, name: &str) -> Result<(), Error> {
+        if self.options.mode == Some(Mode::Html) {
+            self.check_name(name, "attribute")?;
+            self.writer.write_char(' ')?;
+            self.name(name)?;
+            Ok(())
+        } else {
+            self.attribute(name, name)
+        }
+    }
+    fn start_tag_end(&mut self) -> Result<(), Error> {
+        match self.options.mode {
+            Some(Mode::Html) if self.is_void() => {
+                self.writer.write_char('>')?;
+                self.state.pending = Pending::Void;
+            }
+            Some(Mode::Xhtml) if self.is_void() => self.state.pending = Pending::Open,
+            Some(Mode::Xml) => self.state.pending = Pending::Open,
+            _ => self.writer.write_char('>')?,
+        }
+        Ok(())
+    }
+    fn empty_tag_end(&mut self, ending: &str) -> Result<(), Error> {
+        if ending != "/" || self.options.mode.is_none() {
+            self.name(ending)?;
+            self.writer.write_char('>')?;
+            return Ok(());
+        }
+        match self.options.mode {
+            Some(Mode::Xml) => self.writer.write_str("/>")?,
+            Some(Mode::Xhtml) if self.is_void() => self.writer.write_str(" />")?,
+            Some(Mode::Html) if self.is_void() => self.writer.write_char('>')?,
+            _ => {
+                self.writer.write_str("></")?;
+                tools::escape_guard(&mut self.writer).write_str(&self.state.tag)?;
+                self.writer.write_char('>')?;
+            }
+        }
+        Ok(())
+    }
+    fn end_tag(&mut self, name: &str) -> Result<(), Error> {
+        if name == self.state.tag {
+            match self.state.pending {
+                Pending::None => {}
+                Pending::Void => {
+                    self.state.pending = Pending::None;
+                    self.state.whitespace.clear();
+                    return Ok(());
+                }
+                Pending::Open => {
+                    self.state.pending = Pending::None;
+                    self.state.whitespace.clear();
+                    let ending = if self.options.mode == Some(Mode::Xhtml) {
+                        " />"
+                    } else {
+                        "/>"
+                    };
+                    self.writer.write_str(ending)?;
+                    return Ok(());
+                }
+            }
+        }
+        self.check_name(name, "element")?;
+        self.content()?;
+        self.state.brackets = 0;
+        self.writer.write_str("</")?;
+        self.name(name)?;
+        self.writer.write_char('>')?;
+        Ok(())
+    }
+    fn text(&mut self, text: &str) -> Result<(), Error> {
+        if text.is_empty() {
+            return Ok(());
+        }
+        self.content()?;
+        self.escaped_text(text, self.options.escape)
+    }
+    fn raw_text(&mut self, text: &str) -> Result<(), Error> {
+        if text.is_empty() {
+            return Ok(());
+        }
+        self.content()?;
+        match self.options.mode {
+            // Only what XML needs, so scripts and style sheets are left as readable as they can be.
+            Some(Mode::Xml | Mode::Xhtml) => self.escaped_text(text, Escape::Minimal),
+            Some(Mode::Html) => self.verbatim(text),
+            None => self.escaped_text(text, self.options.escape),
+        }
+    }
+    fn raw(&mut self, markup: &str) -> Result<(), Error> {
+        if markup.is_empty() {
+            return Ok(());
+        }
+        self.content()?;
+        self.verbatim(markup)
+    }
+    fn comment(&mut self, text: &str) -> Result<(), Error> {
+        self.content()?;
+        // XML forbids any `--` and a trailing `-`. HTML only forbids what
+        // would end the comment early or open a nested one.
+        let xml = matches!(self.options.mode, Some(Mode::Xml | Mode::Xhtml));
+        let invalid = if xml {
+            text.contains("--") || text.ends_with('-')
+        } else {
+            text.contains("-->")
+                || text.contains("--!>")
+                || text.contains("<!--")
+                || text.ends_with("<!-")
+        };
+        if invalid || text.starts_with('>') || text.starts_with("->") {
+            return Err(Error::validation(format!(
+                "comment {:?} would end early or is not allowed in this mode",
+                text
+            )));
+        }
+        self.writer.write_str("<!--")?;
+        self.verbatim(text)?;
+        self.writer.write_str("-->")?;
+        Ok(())
+    }
+    fn cdata(&mut self, text: &str) -> Result<(), Error> {
+        // HTML only knows CDATA sections inside svg and math.
+        if self.options.mode == Some(Mode::Html) {
+            return self.text(text);
+        }
+        self.content()?;
+        self.writer.write_str("<![CDATA[")?;
+        // A `]]>` in the text ends one section after the `]]` and starts the next before the `>`.
+        for (i, part) in text.split("]]>").enumerate() {
+            if i > 0 {
+                self.writer.write_str("]]]]><![CDATA[>")?;
+            }
+            self.verbatim(part)?;
+        }
+        self.writer.write_str("]]>")?;
+        Ok(())
+    }
+    fn whitespace(&mut self, whitespace: &str) -> Result<(), Error> {
+        if self.state.pending == Pending::None {
+            self.writer.write_str(whitespace)?;
+        } else {
+            self.state.whitespace.push_str(whitespace);
+        }
+        Ok(())
+    }
+}
+
+fn write_guarded<W: fmt::Write>(mut guard: EscapeGuard<W>, s: &str) -> Result<(), Error> {
+    guard.write_str(s).map_err(|e| match guard.rejected() {
+        Some(c) => invalid_char(c),
+        None => e.into(),
+    })
+}
+
+fn invalid_char(c: char) -> Error {
+    Error::validation(format!(
+        "character U+{:04X} is not allowed in XML",
+        c as u32
+    ))
+}
+
+///
+/// Writer that sends everything written to it to a sink as escaped text.
+///
+pub struct TextWriter<'a>(pub(crate) &'a mut dyn Sink, pub(crate) &'a mut Context);
+
+impl fmt::Write for TextWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        // Only HTML writes raw text unescaped, so only there can it end the element early.
+        let res = if self.1.is_raw_text() && self.1.html {
+            self.1.check_raw_text(s).and_then(|_| self.0.raw_text(s))
+        } else if self.1.is_raw_text() {
+            self.0.raw_text(s)
+        } else {
+            self.0.text(s)
+        };
+        res.map_err(|e| self.1.fail(e))
+    }
+}
+
+///
+/// Writer that sends everything written to it to a sink as raw markup.
+///
+pub struct RawWriter<'a>(pub(crate) &'a mut dyn Sink, pub(crate) &'a mut Context);
+
+impl fmt::Write for RawWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let res = if self.1.is_raw_text() {
+            self.1.check_raw_text(s).and_then(|_| self.0.raw(s))
+        } else {
+            self.0.raw(s)
+        };
+        res.map_err(|e| self.1.fail(e))
+    }
+}
+
+///
+/// Writer that sends formatting whitespace to a sink.
+///
+pub(crate) struct WhitespaceWriter<'a>(pub &'a mut dyn Sink, pub &'a mut Context);
+
+impl fmt::Write for WhitespaceWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.whitespace(s).map_err(|e| self.1.fail(e))
+    }
+}
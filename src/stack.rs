@@ -1,4 +1,8 @@
 use super::*;
+use crate::error::BoxError;
+use crate::render::{Fmt, Renderer};
+use crate::sink::{RawWriter, TextWriter};
+use std::marker::PhantomData;
 pub struct Sentinel {
     _p: (),
 }
@@ -24,24 +28,24 @@ pub struct Popper<E, O> {
 pub struct ElemStack<'a, T>(ElemStackEscapable<'a, T>);
 
 impl<'a, T> ElemStack<'a, T> {
-    pub fn put<E: Elem + Locked>(&mut self, elem: E) -> fmt::Result {
+    pub fn put<E: Elem + Locked>(&mut self, elem: E) -> Result<(), Error> {
         self.0.put(elem)
     }
     pub fn push<E: Elem + Locked>(
         self,
         elem: E,
-    ) -> Result<ElemStack<'a, Popper<E::Tail, T>>, fmt::Error> {
-        self.0.push(elem).map(|a| ElemStack(a))
+    ) -> Result<ElemStack<'a, Popper<E::Tail, T>>, Error> {
+        self.0.push(elem).map(ElemStack)
     }
 
-    pub fn writer(&mut self) -> tools::EscapeGuard<WriteWrap<'_>> {
+    pub fn writer(&mut self) -> TextWriter<'_> {
         self.0.writer.writer()
     }
 }
 
 impl<'a, P: Pop> ElemStack<'a, P> {
-    pub fn pop(self) -> Result<ElemStack<'a, P::Last>, fmt::Error> {
-        self.0.pop().map(|a| ElemStack(a))
+    pub fn pop(self) -> Result<ElemStack<'a, P::Last>, Error> {
+        self.0.pop().map(ElemStack)
     }
 }
 
@@ -51,15 +55,21 @@ pub struct ElemStackEscapable<'a, T> {
 }
 
 impl<'a, T> ElemStackEscapable<'a, T> {
-    pub fn put<E: Elem>(&mut self, elem: E) -> fmt::Result {
-        self.writer.render_inner(elem)
+    pub fn put<E: Elem>(&mut self, elem: E) -> Result<(), Error> {
+        let depth = self.writer.2.depth();
+        self.writer
+            .render_inner(elem)
+            .map_err(|e| fail(&mut self.writer, e, depth))
     }
     pub fn push<E: Elem>(
         mut self,
         elem: E,
-    ) -> Result<ElemStackEscapable<'a, Popper<E::Tail, T>>, fmt::Error> {
-        let tail = elem.render_head(self.writer.borrow_mut2())?;
-        Ok(self.push_tail(tail))
+    ) -> Result<ElemStackEscapable<'a, Popper<E::Tail, T>>, Error> {
+        let depth = self.writer.2.depth();
+        match elem.render_head(self.writer.borrow_mut2()) {
+            Ok(tail) => Ok(self.push_tail(tail)),
+            Err(e) => Err(fail(&mut self.writer, e, depth)),
+        }
     }
     fn push_tail<O>(self, tail: O) -> ElemStackEscapable<'a, Popper<O, T>> {
         ElemStackEscapable {
@@ -71,15 +81,18 @@ impl<'a, T> ElemStackEscapable<'a, T> {
         }
     }
 
-    pub fn writer_escapable(&mut self) -> WriteWrap<'_> {
+    pub fn writer_escapable(&mut self) -> RawWriter<'_> {
         self.writer.writer_escapable()
     }
 }
 
 impl<'a, P: Pop> ElemStackEscapable<'a, P> {
-    pub fn pop(mut self) -> Result<ElemStackEscapable<'a, P::Last>, fmt::Error> {
+    pub fn pop(mut self) -> Result<ElemStackEscapable<'a, P::Last>, Error> {
+        let depth = self.writer.2.depth();
         let (e, l) = self.inner.next();
-        e.render(self.writer.borrow_mut2())?;
+        if let Err(e) = e.render(self.writer.borrow_mut2()) {
+            return Err(fail(&mut self.writer, e, depth));
+        }
 
         Ok(ElemStackEscapable {
             writer: self.writer,
@@ -88,59 +101,149 @@ impl<'a, P: Pop> ElemStackEscapable<'a, P> {
     }
 }
 
-pub struct Sess<F> {
+///
+/// Take the error of a failed render and close the tags it left open, back to `depth`.
+///
+fn fail(writer: &mut ElemWrite, e: fmt::Error, depth: usize) -> Error {
+    let e = writer.2.take_error(e);
+    writer.2.unwind(depth);
+    e
+}
+
+///
+/// Element built from a closure that renders into an [`ElemStack`].
+///
+/// A closure with its own error type, see [`build::try_from_stack`], gets it back
+/// from [`Sess::render`]. Rendered as part of other elements, the error is boxed
+/// into a [`tagu::Error`](Error) instead.
+///
+pub struct Sess<F, E = Error> {
     func: F,
+    _p: PhantomData<fn() -> E>,
 }
-impl<F> Sess<F>
+impl<F, E> Sess<F, E>
 where
-    F: FnOnce(ElemStack<Sentinel>) -> Result<ElemStack<Sentinel>, fmt::Error>,
+    F: FnOnce(ElemStack<Sentinel>) -> Result<ElemStack<Sentinel>, E>,
+    E: From<Error>,
 {
     pub fn new(func: F) -> Self {
-        Self { func }
+        Self {
+            func,
+            _p: PhantomData,
+        }
+    }
+
+    ///
+    /// Render the session to a writer, returning the closure's error directly.
+    ///
+    pub fn render<W: fmt::Write>(self, writer: W) -> Result<(), E> {
+        self.render_with(&mut Renderer::new(), writer)
+    }
+
+    ///
+    /// Same as [`Sess::render`] but with the given renderer.
+    ///
+    pub fn render_with<K: Fmt, W: fmt::Write>(
+        self,
+        renderer: &mut Renderer<K>,
+        writer: W,
+    ) -> Result<(), E> {
+        let func = self.func;
+        let mut err = None;
+        let res = renderer.render(
+            Sess::<_, Error>::new(|w| match func(w) {
+                Ok(w) => Ok(w),
+                Err(e) => {
+                    err = Some(e);
+                    Err(Error::from(fmt::Error))
+                }
+            }),
+            writer,
+        );
+        match err {
+            Some(e) => Err(e),
+            None => res.map_err(E::from),
+        }
     }
 }
 
-impl<F> Locked for Sess<F> {}
+impl<F, E> Locked for Sess<F, E> {}
 
-impl<F> Elem for Sess<F>
+impl<F, E> Elem for Sess<F, E>
 where
-    F: FnOnce(ElemStack<Sentinel>) -> Result<ElemStack<Sentinel>, fmt::Error>,
+    F: FnOnce(ElemStack<Sentinel>) -> Result<ElemStack<Sentinel>, E>,
+    E: Into<BoxError>,
 {
     type Tail = ();
-    fn render_head(self, writer: ElemWrite) -> Result<Self::Tail, fmt::Error> {
+    fn render_head(self, mut writer: ElemWrite) -> Result<Self::Tail, fmt::Error> {
         let k = ElemStack(ElemStackEscapable {
-            writer,
+            writer: writer.borrow_mut2(),
             inner: Sentinel { _p: () },
         });
-        let _ = (self.func)(k)?;
-        Ok(())
+        match (self.func)(k) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(writer.2.fail(Error::from_boxed(e.into()))),
+        }
     }
 }
 
-pub struct SessEscapable<F> {
+///
+/// Same as [`Sess`] but renders into an [`ElemStackEscapable`].
+///
+pub struct SessEscapable<F, E = Error> {
     func: F,
+    _p: PhantomData<fn() -> E>,
 }
-impl<F> SessEscapable<F>
+impl<F, E> SessEscapable<F, E>
 where
-    F: FnOnce(ElemStackEscapable<Sentinel>) -> Result<ElemStackEscapable<Sentinel>, fmt::Error>,
+    F: FnOnce(ElemStackEscapable<Sentinel>) -> Result<ElemStackEscapable<Sentinel>, E>,
+    E: From<Error>,
 {
     pub fn new(func: F) -> Self {
-        Self { func }
+        Self {
+            func,
+            _p: PhantomData,
+        }
+    }
+
+    ///
+    /// Render the session to a writer, returning the closure's error directly.
+    ///
+    pub fn render<W: fmt::Write>(self, writer: W) -> Result<(), E> {
+        let func = self.func;
+        let mut err = None;
+        let res = Renderer::new().render_escapable(
+            SessEscapable::<_, Error>::new(|w| match func(w) {
+                Ok(w) => Ok(w),
+                Err(e) => {
+                    err = Some(e);
+                    Err(Error::from(fmt::Error))
+                }
+            }),
+            writer,
+        );
+        match err {
+            Some(e) => Err(e),
+            None => res.map_err(E::from),
+        }
     }
 }
 
-impl<F> Elem for SessEscapable<F>
+impl<F, E> Elem for SessEscapable<F, E>
 where
-    F: FnOnce(ElemStackEscapable<Sentinel>) -> Result<ElemStackEscapable<Sentinel>, fmt::Error>,
+    F: FnOnce(ElemStackEscapable<Sentinel>) -> Result<ElemStackEscapable<Sentinel>, E>,
+    E: Into<BoxError>,
 {
     type Tail = ();
-    fn render_head(self, writer: ElemWrite) -> Result<Self::Tail, fmt::Error> {
+    fn render_head(self, mut writer: ElemWrite) -> Result<Self::Tail, fmt::Error> {
         let k = ElemStackEscapable {
-            writer,
+            writer: writer.borrow_mut2(),
             inner: Sentinel { _p: () },
         };
-        let _ = (self.func)(k)?;
-        Ok(())
+        match (self.func)(k) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(writer.2.fail(Error::from_boxed(e.into()))),
+        }
     }
 }
 
@@ -148,19 +251,21 @@ where
 /// If you dont want to use a closure, you can implement this trait
 ///
 pub trait ElemOuter {
-    fn render<'a>(self, w: ElemStack<'a, Sentinel>) -> Result<ElemStack<'a, Sentinel>, fmt::Error>;
+    fn render<'a>(self, w: ElemStack<'a, Sentinel>) -> Result<ElemStack<'a, Sentinel>, Error>;
 }
 impl<E: ElemOuter> Locked for E {}
 impl<E: ElemOuter> Elem for E {
     type Tail = ();
 
-    fn render_head(self, writer: ElemWrite) -> Result<Self::Tail, fmt::Error> {
+    fn render_head(self, mut writer: ElemWrite) -> Result<Self::Tail, fmt::Error> {
         let k = ElemStack(ElemStackEscapable {
-            writer,
+            writer: writer.borrow_mut2(),
             inner: Sentinel { _p: () },
         });
 
-        let _ = self.render(k)?;
-        Ok(())
+        match self.render(k) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(writer.2.fail(e)),
+        }
     }
 }
@@ -11,40 +11,266 @@ pub fn escape_guard<T: std::fmt::Write>(a: T) -> EscapeGuard<T> {
     EscapeGuard::new(a)
 }
 
+///
+/// Which characters an [`EscapeGuard`] escapes.
+///
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Escape {
+    ///
+    /// Only what the markup requires: `<` and `&`, `>` after `]]` in text,
+    /// and `"` in attribute values.
+    ///
+    Minimal,
+    /// All of `"` `'` `<` `>` `&`.
+    #[default]
+    Full,
+    /// Like `Full`, and every non-ASCII character is written as a numeric character reference.
+    AsciiSafe,
+}
+
 /// Writer adaptor that replaces xml escaping characters with their encoded value.
 ///
-/// Disallowed characters are `"` `'` `<` `>` `&`. characters are replaced with their equivalent from:
+/// By default the disallowed characters are `"` `'` `<` `>` `&`. characters are replaced with their equivalent from:
 /// [https://dev.w3.org/html5/html-author/charref](https://dev.w3.org/html5/html-author/charref)
 ///
+/// See [`Escape`] for the other policies.
+///
 pub struct EscapeGuard<T> {
     writer: T,
+    policy: Escape,
+    invalid: InvalidChars,
+    attribute: bool,
+    rejected: Option<char>,
+    /// How many `]` the output ends with, up to two.
+    brackets: usize,
+}
+
+///
+/// What an [`EscapeGuard`] does with characters that XML 1.0 does not allow,
+/// such as most control characters. They are never valid, even escaped.
+///
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum InvalidChars {
+    /// Fail the write. See [`EscapeGuard::rejected`].
+    #[default]
+    Error,
+    /// Leave them out.
+    Drop,
+    /// Write U+FFFD REPLACEMENT CHARACTER instead.
+    Replace,
+    /// Write them as they are. What [`escape_guard`] does unless told otherwise.
+    Keep,
+}
+
+///
+/// Check if a string matches the `Name` production of XML 1.0.
+///
+/// ```
+/// use tagu::tools::is_xml_name;
+/// assert!(is_xml_name("svg:rect"));
+/// assert!(is_xml_name("data-x.y_z"));
+/// assert!(!is_xml_name("my tag"));
+/// assert!(!is_xml_name("1st"));
+/// ```
+///
+pub fn is_xml_name(name: &str) -> bool {
+    fn is_start(c: char) -> bool {
+        matches!(c,
+            ':' | 'A'..='Z' | '_' | 'a'..='z' | '\u{C0}'..='\u{D6}' | '\u{D8}'..='\u{F6}'
+            | '\u{F8}'..='\u{2FF}' | '\u{370}'..='\u{37D}' | '\u{37F}'..='\u{1FFF}'
+            | '\u{200C}'..='\u{200D}' | '\u{2070}'..='\u{218F}' | '\u{2C00}'..='\u{2FEF}'
+            | '\u{3001}'..='\u{D7FF}' | '\u{F900}'..='\u{FDCF}' | '\u{FDF0}'..='\u{FFFD}'
+            | '\u{10000}'..='\u{EFFFF}')
+    }
+    let mut chars = name.chars();
+    chars.next().is_some_and(is_start)
+        && chars.all(|c| {
+            is_start(c)
+                || matches!(c,
+                    '-' | '.' | '0'..='9' | '\u{B7}' | '\u{300}'..='\u{36F}' | '\u{203F}'..='\u{2040}')
+        })
+}
+
+///
+/// Check if a char matches the `Char` production of XML 1.0.
+///
+pub fn is_xml_char(c: char) -> bool {
+    matches!(c, '\t' | '\n' | '\r' | ' '..='\u{D7FF}' | '\u{E000}'..='\u{FFFD}' | '\u{10000}'..)
 }
 
 impl<T: std::fmt::Write> EscapeGuard<T> {
     pub fn new(writer: T) -> EscapeGuard<T> {
-        EscapeGuard { writer }
+        Self::with_policy(writer, Escape::Full)
+    }
+
+    pub fn with_policy(writer: T, policy: Escape) -> EscapeGuard<T> {
+        EscapeGuard {
+            writer,
+            policy,
+            invalid: InvalidChars::Keep,
+            attribute: false,
+            rejected: None,
+            brackets: 0,
+        }
+    }
+
+    ///
+    /// What to do with characters that are not allowed in XML 1.0.
+    /// Defaults to [`InvalidChars::Keep`].
+    ///
+    pub fn invalid_chars(mut self, invalid: InvalidChars) -> Self {
+        self.invalid = invalid;
+        self
+    }
+
+    ///
+    /// The character that made a write fail under [`InvalidChars::Error`].
+    ///
+    pub fn rejected(&self) -> Option<char> {
+        self.rejected
+    }
+
+    ///
+    /// Continue text whose output so far ends with `n` of `]`,
+    /// so [`Escape::Minimal`] can tell if a `>` would end a `]]>`.
+    ///
+    pub(crate) fn after_brackets(mut self, n: usize) -> Self {
+        self.brackets = n.min(2);
+        self
+    }
+
+    ///
+    /// How many `]` the output ends with, up to two.
+    ///
+    pub(crate) fn brackets(&self) -> usize {
+        self.brackets
+    }
+
+    ///
+    /// Escape for a double quoted attribute value rather than text.
+    ///
+    pub fn attribute(mut self) -> Self {
+        self.attribute = true;
+        self
+    }
+}
+
+///
+/// Which bytes may need escaping, so the common case is a single lookup.
+///
+const fn special_bytes(chars: &[u8], non_ascii: bool) -> [bool; 256] {
+    let mut table = [false; 256];
+    // Control chars, and the first byte of U+FFFE and U+FFFF, may not be xml chars.
+    let mut b = 0;
+    while b < 0x20 {
+        table[b] = !matches!(b, 0x09 | 0x0A | 0x0D);
+        b += 1;
+    }
+    table[0xEF] = true;
+    let mut i = 0;
+    while i < chars.len() {
+        table[chars[i] as usize] = true;
+        i += 1;
+    }
+    // Only the first byte of a non-ascii char, so each char is found once.
+    let mut b = 0xC0;
+    while non_ascii && b < 256 {
+        table[b] = true;
+        b += 1;
+    }
+    table
+}
+
+static SPECIAL_FULL: [bool; 256] = special_bytes(b"\"'<>&", false);
+static SPECIAL_ASCII_SAFE: [bool; 256] = special_bytes(b"\"'<>&", true);
+static SPECIAL_MINIMAL_TEXT: [bool; 256] = special_bytes(b"<>&", false);
+static SPECIAL_MINIMAL_ATTR: [bool; 256] = special_bytes(b"\"<&", false);
+
+impl<T: std::fmt::Write> EscapeGuard<T> {
+    ///
+    /// Write a run of output, keeping count of the `]` it ends with.
+    ///
+    fn put(&mut self, s: &str) -> fmt::Result {
+        if s.is_empty() {
+            return Ok(());
+        }
+        let run = s.bytes().rev().take_while(|&b| b == b']').count();
+        self.brackets = if run == s.len() {
+            (self.brackets + run).min(2)
+        } else {
+            run.min(2)
+        };
+        self.writer.write_str(s)
     }
 }
 
 impl<T: std::fmt::Write> std::fmt::Write for EscapeGuard<T> {
     fn write_str(&mut self, s: &str) -> Result<(), std::fmt::Error> {
-        for c in s.chars() {
-            let r = match c {
-                '\"' => Some("&quot;"),
-                '\'' => Some("&apos;"),
-                '<' => Some("&lt;"),
-                '>' => Some("&gt;"),
-                '&' => Some("&amp;"),
-                _ => None,
-            };
+        let special = match self.policy {
+            Escape::Full => &SPECIAL_FULL,
+            Escape::AsciiSafe => &SPECIAL_ASCII_SAFE,
+            Escape::Minimal if self.attribute => &SPECIAL_MINIMAL_ATTR,
+            Escape::Minimal => &SPECIAL_MINIMAL_TEXT,
+        };
+        let bytes = s.as_bytes();
 
-            if let Some(r) = r {
-                self.writer.write_str(r)?;
-            } else {
-                self.writer.write_char(c)?;
-            }
+        // Write the runs in between special bytes in one go.
+        // The special bytes start a char, so the indices are char boundaries.
+        let mut start = 0;
+        let mut i = 0;
+        while let Some(offset) = bytes[i..].iter().position(|&b| special[b as usize]) {
+            i += offset;
+            let r = match bytes[i] {
+                b'<' => "&lt;",
+                b'&' => "&amp;",
+                b'"' => "&quot;",
+                b'\'' => "&apos;",
+                b'>' if self.policy != Escape::Minimal => "&gt;",
+                // Minimal text only needs it to avoid writing `]]>`,
+                // and the `]]` may have come from an earlier write.
+                b'>' => {
+                    self.put(&s[start..i])?;
+                    start = i;
+                    if self.brackets < 2 {
+                        i += 1;
+                        continue;
+                    }
+                    "&gt;"
+                }
+                _ => {
+                    let c = s[i..].chars().next().unwrap();
+                    let valid = is_xml_char(c) || self.invalid == InvalidChars::Keep;
+                    if valid && (c.is_ascii() || self.policy != Escape::AsciiSafe) {
+                        i += c.len_utf8();
+                        continue;
+                    }
+                    self.put(&s[start..i])?;
+                    match self.invalid {
+                        _ if valid => {
+                            write!(self.writer, "&#x{:X};", c as u32)?;
+                            self.brackets = 0;
+                        }
+                        InvalidChars::Error => {
+                            self.rejected = Some(c);
+                            return Err(fmt::Error);
+                        }
+                        InvalidChars::Drop | InvalidChars::Keep => {}
+                        InvalidChars::Replace if self.policy == Escape::AsciiSafe => {
+                            self.put("&#xFFFD;")?
+                        }
+                        InvalidChars::Replace => self.put("\u{FFFD}")?,
+                    }
+                    i += c.len_utf8();
+                    start = i;
+                    continue;
+                }
+            };
+            self.put(&s[start..i])?;
+            self.put(r)?;
+            i += 1;
+            start = i;
         }
-        Ok(())
+        self.put(&s[start..])
     }
 }
 
@@ -52,6 +278,8 @@ impl<T: std::fmt::Write> std::fmt::Write for EscapeGuard<T> {
 /// Used to wrap a `std::io::Write` to have `std::fmt::Write`.
 /// The underlying error can be extracted through the error field.
 ///
+/// Consider using [`crate::render_io`] instead, which buffers and returns the `io::Error` directly.
+///
 pub struct Adaptor<T> {
     pub inner: T,
     pub error: Result<(), std::io::Error>,
@@ -79,6 +307,59 @@ impl<T: std::io::Write> std::fmt::Write for Adaptor<T> {
     }
 }
 
+///
+/// Write a file through a temporary file next to `path` that is then renamed over it.
+/// So `path` either keeps its old contents or gets everything `write` wrote.
+///
+/// The temporary file has a name unique to the process and call,
+/// so concurrent writers of the same path do not interfere.
+///
+/// ```
+/// use std::io::Write;
+/// let path = std::env::temp_dir().join("tagu_write_file_atomic.txt");
+/// tagu::tools::write_file_atomic(&path, |file| file.write_all(b"hello")).unwrap();
+/// assert_eq!(std::fs::read_to_string(&path).unwrap(), "hello");
+/// ```
+///
+pub fn write_file_atomic<P: AsRef<std::path::Path>>(
+    path: P,
+    write: impl FnOnce(&mut std::fs::File) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let path = path.as_ref();
+    let name = path.file_name().ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "path does not name a file",
+        )
+    })?;
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(name);
+    tmp_name.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let tmp = path.with_file_name(tmp_name);
+
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&tmp)?;
+    let res = write(&mut file).and_then(|_| file.sync_all());
+    drop(file);
+
+    match res.and_then(|_| std::fs::rename(&tmp, path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = std::fs::remove_file(&tmp);
+            Err(e)
+        }
+    }
+}
+
 ///
 /// Similar to `std::format_args!()` except has a more flexible lifetime.
 /// Shorthand for `disp_const(move |w|write!(w,...))`
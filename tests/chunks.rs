@@ -0,0 +1,101 @@
+use tagu::build;
+use tagu::prelude::*;
+use tagu::render::{Mode, RenderOptions};
+
+#[test]
+fn chunks_collect_up_to_chunk_size() {
+    let rows = || (0..50).map(|i| build::elem("p").append(build::raw(i)).inline());
+    let mut whole = String::new();
+    tagu::render(
+        build::elem("ul").append(build::from_iter(rows())),
+        &mut whole,
+    )
+    .unwrap();
+
+    let mut renderer = RenderOptions::new().chunk_size(64).build();
+    let chunks: Vec<String> = renderer
+        .chunks(build::elem("ul"), rows())
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(chunks.concat(), whole);
+    assert!(chunks.len() > 1);
+    for chunk in &chunks[..chunks.len() - 1] {
+        assert!(chunk.len() >= 64 && chunk.len() < 64 + 16, "{:?}", chunk);
+    }
+
+    let mut renderer = RenderOptions::new().build();
+    let mut chunks = renderer.chunks(build::elem("ul"), rows());
+    assert_eq!(chunks.next().unwrap().unwrap(), whole);
+    assert!(chunks.next().is_none());
+}
+
+#[test]
+fn chunks_never_split_a_tag() {
+    let rows = || (0..2).map(|i| build::elem("p").append(build::raw(i)).inline());
+    let mut renderer = RenderOptions::new().chunk_size(0).build();
+    let chunks: Vec<String> = renderer
+        .chunks(build::elem("ul"), rows())
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        chunks,
+        ["<ul>\n", "\t<p>0</p>\n", "\t<p>1</p>\n", "</ul>\n"]
+    );
+
+    // In Xml the `>` of `<ul` waits for the first child, so both go in one chunk.
+    let mut renderer = RenderOptions::new().mode(Mode::Xml).chunk_size(0).build();
+    let chunks: Vec<String> = renderer
+        .chunks(build::elem("ul"), rows())
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(chunks, ["<ul>\n\t<p>0</p>\n", "\t<p>1</p>\n", "</ul>\n"]);
+}
+
+#[test]
+fn chunks_reset_the_formatter() {
+    let mut renderer = RenderOptions::new().chunk_size(0).build();
+    let deep = build::elem("a").append(build::elem("b")).with_tab("  ");
+    let mut chunks = renderer.chunks(deep, (0..3).map(|_| build::elem("c").inline()));
+    assert_eq!(chunks.next().unwrap().unwrap(), "<a>\n  <b>\n  </b>\n");
+    drop(chunks);
+
+    let mut s = String::new();
+    renderer
+        .render(build::elem("x").append(build::elem("y")), &mut s)
+        .unwrap();
+    assert_eq!(s, "<x>\n\t<y>\n\t</y>\n</x>\n");
+
+    let mut chunks = renderer.chunks(build::elem("a").inline(), [build::elem("b")]);
+    assert_eq!(chunks.next().unwrap().unwrap(), "<a>");
+    drop(chunks);
+
+    let mut s = String::new();
+    renderer
+        .render(build::elem("x").append(build::elem("y")), &mut s)
+        .unwrap();
+    assert_eq!(s, "<x>\n\t<y>\n\t</y>\n</x>\n");
+
+    // A render that fails part way leaves it alone too.
+    let bad = build::elem("x").append(build::elem("y").append(build::single("bad name")));
+    assert!(renderer.render(bad, &mut String::new()).is_err());
+    let mut s = String::new();
+    renderer
+        .render(build::elem("x").append(build::elem("y")), &mut s)
+        .unwrap();
+    assert_eq!(s, "<x>\n\t<y>\n\t</y>\n</x>\n");
+}
+
+#[test]
+fn chunks_stop_after_an_error() {
+    let mut renderer = RenderOptions::new().chunk_size(0).build();
+    let children = [
+        build::single("ok"),
+        build::single("bad name"),
+        build::single("ok"),
+    ];
+    let mut chunks = renderer.chunks(build::elem("a"), children);
+    assert_eq!(chunks.next().unwrap().unwrap(), "<a>\n");
+    assert_eq!(chunks.next().unwrap().unwrap(), "\t<ok/>\n");
+    assert!(chunks.next().unwrap().is_err());
+    assert!(chunks.next().is_none());
+}
@@ -0,0 +1,163 @@
+use tagu::build;
+use tagu::dom::{self, Document};
+use tagu::prelude::*;
+use tagu::render::{Mode, RenderOptions};
+
+fn render(doc: &Document, mode: Option<Mode>) -> String {
+    let mut options = RenderOptions::new();
+    if let Some(mode) = mode {
+        options = options.mode(mode);
+    }
+    let mut s = String::new();
+    options.build().render(doc, &mut s).unwrap();
+    s
+}
+
+#[test]
+fn empty_elements_keep_their_form() {
+    let doc = tagu::parse::xml("<g><rect/><text></text></g>").unwrap();
+    assert_eq!(
+        render(&doc, None),
+        "<g>\n\t<rect/>\n\t<text></text>\n</g>\n"
+    );
+
+    let doc = tagu::parse::html("<div><br><p></p><input disabled></div>").unwrap();
+    assert_eq!(
+        render(&doc, Some(Mode::Html)),
+        "<div>\n\t<br>\n\t<p></p>\n\t<input disabled=\"\">\n</div>\n"
+    );
+}
+
+#[test]
+fn captured_elements_keep_their_form() {
+    let all = build::elem("g")
+        .append(build::single("rect"))
+        .append(build::elem("text"));
+    let nodes = dom::capture(all).unwrap();
+    let g = nodes[0].as_element().unwrap();
+    assert!(!g.self_closing);
+    let forms: Vec<_> = g.elements().map(|e| e.self_closing).collect();
+    assert_eq!(forms, [true, false]);
+
+    let doc = Document::capture(
+        build::elem("g")
+            .append(build::single("rect"))
+            .append(build::elem("text")),
+    )
+    .unwrap();
+    assert_eq!(
+        render(&doc, None),
+        "<g>\n\t<rect/>\n\t<text></text>\n</g>\n"
+    );
+}
+
+#[test]
+fn capture_joins_text() {
+    let all = build::elem("ul").append(
+        build::elem("li")
+            .with(("class", "first"))
+            .append(build::raw("hello"))
+            .append(build::raw(" world")),
+    );
+    let nodes = dom::capture(all).unwrap();
+    let ul = nodes[0].as_element().unwrap();
+    let li = ul.elements().next().unwrap();
+    assert_eq!(ul.name, "ul");
+    assert_eq!(li.attr("class"), Some("first"));
+    assert_eq!(li.children, [dom::Node::Text("hello world".to_string())]);
+}
+
+#[test]
+fn tree_sink_left_open() {
+    use tagu::sink::Sink;
+    let mut sink = tagu::dom::TreeSink::new();
+    sink.start_tag("a").unwrap();
+    let err = sink.into_nodes().unwrap_err();
+    assert_eq!(err.to_string(), "element a is not closed");
+
+    let mut sink = tagu::dom::TreeSink::new();
+    sink.start_tag("a").unwrap();
+    assert!(sink.end_tag("b").is_err());
+    assert!(tagu::dom::TreeSink::new().end_tag("a").is_err());
+}
+
+#[test]
+fn created_elements_are_self_closing_until_changed() {
+    let mut doc = Document::new();
+    let root = doc.root();
+    let a = doc.append_element(root, "a");
+    assert!(doc.self_closing(a));
+    assert_eq!(render(&doc, None), "<a/>\n");
+
+    doc.set_self_closing(a, false);
+    assert_eq!(render(&doc, None), "<a></a>\n");
+    let text = doc.append_text(a, "x");
+    assert!(!doc.self_closing(text));
+}
+
+#[test]
+fn patch_after_building() {
+    let mut doc = Document::new();
+    let root = doc.root();
+    let layout = doc.append_element(root, "div");
+    let p = doc.append_element(layout, "p");
+    doc.append_text(p, "hello");
+    doc.add_class(layout, "wide");
+    doc.add_class(layout, "wide");
+    assert_eq!(
+        render(&doc, None),
+        "<div class=\"wide\">\n\t<p>hello</p>\n</div>\n"
+    );
+
+    doc.remove(p);
+    assert_eq!(render(&doc, None), "<div class=\"wide\"/>\n");
+    doc.append_child(root, p);
+    assert_eq!(render(&doc, None), "<div class=\"wide\"/>\n<p>hello</p>\n");
+}
+
+#[test]
+fn deep_documents_render() {
+    let mut doc = Document::new();
+    let mut parent = doc.root();
+    for _ in 0..100_000 {
+        parent = doc.append_element(parent, "b");
+        doc.append_text(parent, "x");
+    }
+    let s = render(&doc, None);
+    assert!(s.starts_with("<b>x<b>x<b>"));
+    assert!(s.contains("x<b>x</b></b>"));
+    assert!(s.ends_with("</b></b>\n"));
+}
+
+#[test]
+fn whitespace_is_kept_inside_text() {
+    let doc = tagu::parse::xml("<p>a <b> </b><i><u>b</u> c</i></p>").unwrap();
+    assert_eq!(render(&doc, None), "<p>a <b> </b><i><u>b</u> c</i></p>\n");
+}
+
+#[test]
+fn only_declarations_are_written_as_is() {
+    let doc = tagu::parse::xml("<?xml version='1.0'?><!DOCTYPE svg><svg/>").unwrap();
+    assert_eq!(
+        render(&doc, None),
+        "<?xml version='1.0'?>\n<!DOCTYPE svg>\n<svg/>\n"
+    );
+
+    // The parser takes anything up to the first `>` as a declaration.
+    let doc = tagu::parse::xml("<p><!a </p>></p>").unwrap();
+    let err = tagu::render(&doc, &mut String::new()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "raw markup \"<!a </p>\" is not a declaration (at p)"
+    );
+
+    // Html ends a declaration at its first `>`, even inside an internal subset.
+    let doc = tagu::parse::xml("<!DOCTYPE a [<!ENTITY b 'c'>]><a/>").unwrap();
+    assert!(!render(&doc, None).is_empty());
+    let err = RenderOptions::new()
+        .mode(Mode::Html)
+        .build()
+        .render(&doc, &mut String::new())
+        .unwrap_err();
+    assert!(err.to_string().contains("is not a declaration"));
+}
@@ -0,0 +1,36 @@
+use std::fmt;
+use tagu::build;
+use tagu::error::ErrorKind;
+use tagu::prelude::*;
+
+struct FailingFmt;
+
+impl fmt::Write for FailingFmt {
+    fn write_str(&mut self, _: &str) -> fmt::Result {
+        Err(fmt::Error)
+    }
+}
+
+#[test]
+fn writer_fails() {
+    let err = tagu::render(build::single("a"), FailingFmt).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::Fmt));
+    assert_eq!(err.to_string(), "an error occurred when formatting (at a)");
+}
+
+#[test]
+fn user_error_in_session() {
+    let all = build::elem("svg").append(build::from_stack(|w| {
+        let w = w.push(build::elem("g"))?;
+        "x".parse::<u32>().map_err(tagu::Error::user)?;
+        w.pop()
+    }));
+    let err = tagu::render(all, &mut String::new()).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::User(_)));
+    assert_eq!(err.path(), ["svg", "g"]);
+    assert_eq!(
+        err.to_string(),
+        "invalid digit found in string (at svg > g)"
+    );
+    assert!(std::error::Error::source(&err).is_some());
+}
@@ -0,0 +1,265 @@
+use std::fmt::Write;
+use tagu::build;
+use tagu::prelude::*;
+use tagu::render::{Mode, RenderOptions};
+use tagu::tools::{Escape, EscapeGuard};
+
+fn render_escape<E: Elem + tagu::elem::Locked>(escape: Escape, elem: E) -> String {
+    let mut s = String::new();
+    RenderOptions::new()
+        .escape(escape)
+        .trailing_newline(false)
+        .build()
+        .render(elem, &mut s)
+        .unwrap();
+    s
+}
+
+#[test]
+fn escape_policies() {
+    let all = || {
+        build::elem("p")
+            .with(("title", "\"ok\" > 'no'"))
+            .append(build::raw("café > 3 & 4"))
+            .inline()
+    };
+    assert_eq!(
+        render_escape(Escape::Minimal, all()),
+        "<p title=\"&quot;ok&quot; > 'no'\">café > 3 &amp; 4</p>"
+    );
+    assert_eq!(
+        render_escape(Escape::Full, all()),
+        "<p title=\"&quot;ok&quot; &gt; &apos;no&apos;\">café &gt; 3 &amp; 4</p>"
+    );
+    assert_eq!(
+        render_escape(Escape::AsciiSafe, all()),
+        "<p title=\"&quot;ok&quot; &gt; &apos;no&apos;\">caf&#xE9; &gt; 3 &amp; 4</p>"
+    );
+}
+
+#[test]
+fn ascii_safe_leaves_names_alone() {
+    let all = build::elem("café")
+        .with(("naïve", "é"))
+        .append(build::raw("é"))
+        .inline();
+    assert_eq!(
+        render_escape(Escape::AsciiSafe, all),
+        "<café naïve=\"&#xE9;\">&#xE9;</café>"
+    );
+    assert_eq!(
+        render_escape(Escape::AsciiSafe, build::single("café")),
+        "<café/>"
+    );
+}
+
+#[test]
+fn minimal_finds_cdata_end_across_writes() {
+    let mut s = String::new();
+    let mut w = EscapeGuard::with_policy(&mut s, Escape::Minimal);
+    w.write_str("a]").unwrap();
+    w.write_str("]").unwrap();
+    w.write_str(">b>").unwrap();
+    w.write_str("]]").unwrap();
+    w.write_str("&>").unwrap();
+    assert_eq!(s, "a]]&gt;b>]]&amp;>");
+
+    let all = build::elem("p")
+        .append(build::raw("x]]"))
+        .append(build::raw(">"))
+        .append(build::raw("]"))
+        .append(build::raw("]"))
+        .append(build::raw(">"))
+        .inline();
+    assert_eq!(render_escape(Escape::Minimal, all), "<p>x]]&gt;]]&gt;</p>");
+
+    let all = build::elem("p")
+        .append(build::raw("]]"))
+        .append(build::single("b"))
+        .append(build::raw(">"))
+        .inline();
+    assert_eq!(render_escape(Escape::Minimal, all), "<p>]]<b/>></p>");
+}
+
+#[test]
+fn invalid_chars_in_text_and_attributes() {
+    use tagu::tools::InvalidChars;
+
+    let err = tagu::render(
+        build::elem("p").append(build::raw("\u{1}")),
+        &mut String::new(),
+    );
+    assert_eq!(
+        err.unwrap_err().to_string(),
+        "character U+0001 is not allowed in XML (at p)"
+    );
+
+    let all = || build::single("text").with(("label", "a\u{1B}b"));
+    let err = tagu::render(all(), &mut String::new()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "character U+001B is not allowed in XML (at text)"
+    );
+    let mut s = String::new();
+    RenderOptions::new()
+        .invalid_chars(InvalidChars::Replace)
+        .build()
+        .render(all(), &mut s)
+        .unwrap();
+    assert_eq!(s, "<text label=\"a\u{FFFD}b\"/>\n");
+}
+
+#[test]
+fn invalid_chars_in_raw_markup() {
+    use tagu::tools::InvalidChars;
+
+    let err = tagu::render_escapable(build::raw_escapable("\u{1b}"), &mut String::new());
+    assert_eq!(
+        err.unwrap_err().to_string(),
+        "character U+001B is not allowed in XML"
+    );
+
+    let html = |invalid| {
+        let mut s = String::new();
+        RenderOptions::new()
+            .mode(Mode::Html)
+            .invalid_chars(invalid)
+            .trailing_newline(false)
+            .build()
+            .render(
+                build::elem("script")
+                    .append(build::raw("a\u{1b}b"))
+                    .inline(),
+                &mut s,
+            )
+            .map(|_| s)
+    };
+    assert!(html(InvalidChars::Error).is_err());
+    assert_eq!(html(InvalidChars::Drop).unwrap(), "<script>ab</script>");
+    assert_eq!(
+        html(InvalidChars::Replace).unwrap(),
+        "<script>a\u{FFFD}b</script>"
+    );
+}
+
+#[test]
+fn escape_guard_keeps_invalid_chars_by_default() {
+    let mut s = String::new();
+    write!(tagu::tools::escape_guard(&mut s), "a\u{1b}<").unwrap();
+    assert_eq!(s, "a\u{1b}&lt;");
+}
+
+#[test]
+fn script_is_left_alone_only_in_html() {
+    let script = || {
+        build::elem("script")
+            .append(build::raw("a < b && c > d"))
+            .inline()
+    };
+    let mut s = String::new();
+    RenderOptions::new()
+        .mode(Mode::Html)
+        .build()
+        .render(script(), &mut s)
+        .unwrap();
+    assert_eq!(s, "<script>a < b && c > d</script>\n");
+
+    // The XML modes escape only what they have to.
+    let mut s = String::new();
+    RenderOptions::new()
+        .mode(Mode::Xml)
+        .build()
+        .render(script(), &mut s)
+        .unwrap();
+    assert_eq!(s, "<script>a &lt; b &amp;&amp; c > d</script>\n");
+
+    // Without a mode it is text like any other, so an SVG script is still XML.
+    let mut s = String::new();
+    tagu::render(build::elem("svg").append(script()), &mut s).unwrap();
+    assert_eq!(
+        s,
+        "<svg>\n\t<script>a &lt; b &amp;&amp; c &gt; d</script>\n</svg>\n"
+    );
+}
+
+#[test]
+fn script_end_tag_is_rejected_where_it_is_not_escaped() {
+    let html = || RenderOptions::new().mode(Mode::Html).build();
+    let all = build::elem("SCRIPT")
+        .append(build::raw("a </scr"))
+        .append(build::raw("IPT>"));
+    let err = html().render(all, &mut String::new()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "SCRIPT content cannot contain </script (at SCRIPT)"
+    );
+
+    let all = build::elem("script")
+        .append(build::raw("</script>"))
+        .inline();
+    let mut s = String::new();
+    tagu::render(all, &mut s).unwrap();
+    assert_eq!(s, "<script>&lt;/script&gt;</script>\n");
+
+    let all = build::elem("style").append(build::raw_escapable("</style>"));
+    assert!(tagu::render_escapable(all, &mut String::new()).is_err());
+
+    // Only until the element is closed.
+    let all = build::elem("div")
+        .append(build::elem("style").append(build::raw("a {}")))
+        .append(build::raw("</style>"));
+    let mut s = String::new();
+    html().render(all, &mut s).unwrap();
+    assert!(s.contains("&lt;/style&gt;"));
+}
+
+#[test]
+fn script_cannot_contain_markup() {
+    let all = build::elem("script").append(build::single("b"));
+    let err = tagu::render(all, &mut String::new()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "script cannot contain elements (at script)"
+    );
+
+    // HTML has no comments or CDATA sections there, XML does.
+    let html = || RenderOptions::new().mode(Mode::Html).build();
+    let comment = build::elem("style").append(build::comment("x"));
+    assert!(html().render(comment, &mut String::new()).is_err());
+
+    let cdata = || build::elem("script").append(build::cdata("a < b")).inline();
+    let mut s = String::new();
+    html().render(cdata(), &mut s).unwrap();
+    assert_eq!(s, "<script>a < b</script>\n");
+    let mut s = String::new();
+    tagu::render(cdata(), &mut s).unwrap();
+    assert_eq!(s, "<script><![CDATA[a < b]]></script>\n");
+}
+
+#[test]
+fn url_attributes_are_checked_in_html_modes() {
+    let link = || build::single("a").with(("href", "/a b\"c?x=1&y=2"));
+    let mut s = String::new();
+    RenderOptions::new()
+        .mode(Mode::Xhtml)
+        .build()
+        .render(link(), &mut s)
+        .unwrap();
+    assert_eq!(s, "<a href=\"/a%20b%22c?x=1&amp;y=2\"></a>\n");
+
+    let mut s = String::new();
+    tagu::render(link(), &mut s).unwrap();
+    assert_eq!(s, "<a href=\"/a b&quot;c?x=1&amp;y=2\"/>\n");
+
+    let js = || build::single("a").with(("href", " JavaScript:alert(1)"));
+    let err = RenderOptions::new()
+        .mode(Mode::Html)
+        .build()
+        .render(js(), &mut String::new())
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "attribute href has a JavaScript URL (at a)"
+    );
+    assert!(tagu::render(js(), &mut String::new()).is_ok());
+}
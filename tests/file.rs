@@ -0,0 +1,83 @@
+use std::io;
+use tagu::build;
+use tagu::error::ErrorKind;
+use tagu::prelude::*;
+
+struct FailingIo;
+
+impl io::Write for FailingIo {
+    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"))
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn concurrent_render_file_to_one_path() {
+    let dir = std::env::temp_dir().join(format!("tagu_concurrent_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("out.svg");
+
+    let threads: Vec<_> = (0..8)
+        .map(|i| {
+            let path = path.clone();
+            std::thread::spawn(move || {
+                let all = build::elem("svg").append(build::from_iter(
+                    (0..200).map(move |j| build::single("rect").with(("x", i * j))),
+                ));
+                tagu::render_file(all, &path)
+            })
+        })
+        .collect();
+    for t in threads {
+        t.join().unwrap().unwrap();
+    }
+
+    // One complete render won, and no temporary files are left behind.
+    let out = std::fs::read_to_string(&path).unwrap();
+    assert!(out.starts_with("<svg>\n") && out.ends_with("</svg>\n"));
+    assert_eq!(out.lines().count(), 202);
+    let left: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+    assert_eq!(left.len(), 1);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn failed_render_file_keeps_old_contents() {
+    let dir = std::env::temp_dir().join(format!("tagu_failed_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("out.svg");
+    std::fs::write(&path, "old").unwrap();
+
+    assert!(tagu::render_file(build::single("bad name"), &path).is_err());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "old");
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn io_writer_fails() {
+    let all = build::elem("a").append(build::from_iter(
+        (0..10_000).map(|i| build::single("b").with(("i", i))),
+    ));
+    let err = tagu::render_io(all, FailingIo).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    assert_eq!(err.to_string(), "closed");
+
+    // A validation error comes through io::Error with the tagu::Error inside.
+    let err = tagu::render_io(build::single("bad name"), Vec::new()).unwrap_err();
+    let inner = err.into_inner().unwrap();
+    let inner = inner.downcast::<tagu::Error>().unwrap();
+    assert!(matches!(inner.kind(), ErrorKind::Validation(_)));
+}
+
+#[test]
+fn render_file_into_missing_directory() {
+    let path = std::env::temp_dir()
+        .join(format!("tagu_missing_{}", std::process::id()))
+        .join("out.svg");
+    let err = tagu::render_file(build::single("svg"), &path).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+}
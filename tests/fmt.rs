@@ -0,0 +1,85 @@
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn tagu_fmt(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_tagu-fmt"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn temp_file(name: &str, contents: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("tagu_fmt_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn check_fails_until_written() {
+    let path = temp_file("check.svg", "<svg><g><text>a <b>b</b></text></g></svg>");
+    let p = path.to_str().unwrap();
+
+    let out = tagu_fmt(&["--check", p]);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("not formatted"));
+
+    let out = tagu_fmt(&["--write", p]);
+    assert!(out.status.success());
+    assert!(out.stdout.is_empty());
+    let formatted = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(
+        formatted,
+        "<svg>\n\t<g>\n\t\t<text>a <b>b</b></text>\n\t</g>\n</svg>\n"
+    );
+
+    assert!(tagu_fmt(&["--check", p]).status.success());
+    let out = tagu_fmt(&[p]);
+    assert_eq!(String::from_utf8_lossy(&out.stdout), formatted);
+}
+
+#[test]
+fn write_leaves_file_alone_on_parse_error() {
+    let path = temp_file("broken.svg", "<svg><g></svg>");
+    let out = tagu_fmt(&["-w", path.to_str().unwrap()]);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("does not match"));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "<svg><g></svg>");
+}
+
+#[test]
+fn usage() {
+    let out = tagu_fmt(&["--help"]);
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stdout).starts_with("Usage: tagu-fmt"));
+
+    let out = tagu_fmt(&["--bogus"]);
+    assert_eq!(out.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("unknown option --bogus"));
+
+    let out = tagu_fmt(&["--write"]);
+    assert_eq!(out.status.code(), Some(2));
+}
+
+#[test]
+fn write_keeps_entities_and_is_idempotent() {
+    let src = "<body><p title='&quot;&rarr;'>&rarr;&eacute; &amp;rarr; &lt;b&gt; &copy 2024</p>\
+               <script>if (a < b && c) {}</script></body>";
+    let path = temp_file("entities.html", src);
+    let p = path.to_str().unwrap();
+
+    assert!(tagu_fmt(&["--write", p]).status.success());
+    let formatted = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(
+        formatted,
+        "<body>\n\
+         \t<p title=\"&quot;→\">→é &amp;rarr; &lt;b&gt; © 2024</p>\n\
+         \t<script>if (a < b && c) {}</script>\n\
+         </body>\n"
+    );
+
+    assert!(tagu_fmt(&["--write", p]).status.success());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), formatted);
+    assert!(tagu_fmt(&["--check", p]).status.success());
+}
@@ -0,0 +1,99 @@
+use tagu::build;
+use tagu::ns::{self, Namespace};
+use tagu::prelude::*;
+use tagu::render::{DuplicateAttrs, Mode, RenderOptions};
+
+fn xml() -> RenderOptions {
+    RenderOptions::new().mode(Mode::Xml).trailing_newline(false)
+}
+
+#[test]
+fn namespace_chosen_at_runtime() {
+    let chart = Namespace::from_parts(String::from("c"), format!("urn:{}", "chart"));
+    assert_eq!(chart.prefix(), "c");
+    assert_eq!(chart.uri(), "urn:chart");
+
+    let all = build::elem_ns(chart.clone(), "chart")
+        .append(build::single_ns(chart.clone(), "series").with(build::attr_ns(chart, "id", 1)))
+        .inline();
+    let mut s = String::new();
+    xml().build().render(all, &mut s).unwrap();
+    assert_eq!(
+        s,
+        "<c:chart xmlns:c=\"urn:chart\"><c:series c:id=\"1\"/></c:chart>"
+    );
+}
+
+#[test]
+fn namespaces_are_declared_where_first_needed() {
+    const CHART: Namespace = Namespace::new("c", "urn:chart");
+    let link = || build::single("use").with(build::attr_ns(ns::XLINK, "href", "#a"));
+    let all = build::elem_ns(ns::SVG, "svg")
+        .append(build::single_ns(CHART, "series").with(build::attr_ns(CHART, "id", 1)))
+        .append(build::single_ns(CHART, "series"))
+        .append(link())
+        .append(
+            build::elem("g")
+                .with(build::xmlns(ns::XLINK))
+                .append(link()),
+        );
+    let mut s = String::new();
+    xml().build().render(all, &mut s).unwrap();
+    assert_eq!(
+        s,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\">\n\
+         \t<c:series xmlns:c=\"urn:chart\" c:id=\"1\"/>\n\
+         \t<c:series xmlns:c=\"urn:chart\"/>\n\
+         \t<use xmlns:xlink=\"http://www.w3.org/1999/xlink\" xlink:href=\"#a\"/>\n\
+         \t<g xmlns:xlink=\"http://www.w3.org/1999/xlink\">\n\
+         \t\t<use xlink:href=\"#a\"/>\n\
+         \t</g>\n\
+         </svg>"
+    );
+
+    // A fragment can rely on a declaration it goes inside of.
+    let mut s = String::new();
+    xml()
+        .namespace(ns::XLINK)
+        .build()
+        .render(link(), &mut s)
+        .unwrap();
+    assert_eq!(s, "<use xlink:href=\"#a\"/>");
+}
+
+#[test]
+fn undeclared_prefixes_are_only_checked_in_xml_modes() {
+    let all = || build::single("c:series").with(("x:id", 1));
+    let err = xml().build().render(all(), &mut String::new()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "namespace prefix \"c\" of \"c:series\" is not declared (at c:series)"
+    );
+
+    // Html and output without a mode leave prefixes alone.
+    let mut s = String::new();
+    tagu::render(all(), &mut s).unwrap();
+    assert_eq!(s, "<c:series x:id=\"1\"/>\n");
+    let mut html = RenderOptions::new().mode(Mode::Html).build();
+    assert!(html.render(all(), &mut String::new()).is_ok());
+}
+
+#[test]
+fn replaced_declaration_binds_the_new_uri() {
+    let all = || {
+        build::elem("p:a")
+            .with(("xmlns:p", "urn:old"))
+            .with(("xmlns:p", "urn:new"))
+            .append(build::single_ns(Namespace::new("p", "urn:new"), "b"))
+            .inline()
+    };
+    for policy in [DuplicateAttrs::LastWins, DuplicateAttrs::Merge] {
+        let mut s = String::new();
+        xml()
+            .duplicate_attrs(policy)
+            .build()
+            .render(all(), &mut s)
+            .unwrap();
+        assert_eq!(s, "<p:a xmlns:p=\"urn:new\"><p:b/></p:a>");
+    }
+}
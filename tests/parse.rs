@@ -0,0 +1,174 @@
+use tagu::dom::Document;
+use tagu::parse::{self, ParseOptions};
+use tagu::render::{Mode, RenderOptions};
+
+fn render(doc: &Document) -> String {
+    let mut s = String::new();
+    tagu::render(doc, &mut s).unwrap();
+    s
+}
+
+fn xml_error(src: &str) -> String {
+    parse::xml(src).err().unwrap().to_string()
+}
+
+#[test]
+fn xml_error_messages() {
+    assert_eq!(
+        xml_error("<a>\n  <b></a>"),
+        "end tag a does not match b at line 2, column 10 (at a > b)"
+    );
+    assert_eq!(
+        xml_error("</a>"),
+        "end tag a without a start tag at line 1, column 5"
+    );
+    assert_eq!(
+        xml_error("<a><b>"),
+        "element b is not closed at line 1, column 7 (at a > b)"
+    );
+    assert_eq!(
+        xml_error("<a x>"),
+        "attribute x has no value at line 1, column 5 (at a)"
+    );
+    assert_eq!(
+        xml_error("<a x='1' x='2'/>"),
+        "duplicate attribute x at line 1, column 15 (at a)"
+    );
+    assert_eq!(
+        xml_error("<a x=1/>"),
+        "expected a quoted attribute value at line 1, column 6 (at a)"
+    );
+    assert_eq!(
+        xml_error("<a x='1/>"),
+        "unterminated attribute value at line 1, column 7 (at a)"
+    );
+    assert_eq!(
+        xml_error("<a>&nbsp;</a>"),
+        "unknown or unterminated entity at line 1, column 4 (at a)"
+    );
+    assert_eq!(xml_error("a < b"), "unexpected < at line 1, column 3");
+    assert_eq!(
+        xml_error("<!-- a"),
+        "unterminated comment at line 1, column 5"
+    );
+    assert_eq!(
+        xml_error("<?xml version='1.0'"),
+        "unterminated processing instruction at line 1, column 1"
+    );
+    assert_eq!(
+        xml_error("<!DOCTYPE a [<!ENTITY b 'c'>"),
+        "unterminated declaration at line 1, column 1"
+    );
+}
+
+#[test]
+fn error_column_counts_chars() {
+    assert_eq!(
+        xml_error("<ä>ö</b>"),
+        "end tag b does not match ä at line 1, column 9 (at ä)"
+    );
+}
+
+#[test]
+fn entities_must_be_xml_chars() {
+    assert!(parse::xml("<a>&#0;</a>").is_err());
+    assert!(parse::xml("<a>&#x1;</a>").is_err());
+    assert!(parse::xml("<a>&#xFFFE;</a>").is_err());
+    let doc = parse::xml("<a>&#x9;&#65;&#x1F600;</a>").unwrap();
+    assert_eq!(doc.text(doc.root()), "\tA\u{1F600}");
+
+    // Html leaves entities it does not decode alone.
+    let doc = parse::html("<p>&#0;</p>").unwrap();
+    assert_eq!(doc.text(doc.root()), "&#0;");
+}
+
+#[test]
+fn doctype_internal_subset() {
+    let src = "<!DOCTYPE svg [\n<!ENTITY a \"<b>\">\n]>\n<svg/>";
+    let doc = parse::xml(src).unwrap();
+    assert_eq!(
+        render(&doc),
+        "<!DOCTYPE svg [\n<!ENTITY a \"<b>\">\n]>\n<svg/>\n"
+    );
+}
+
+#[test]
+fn rendering_again_gives_the_same_markup() {
+    let src = "<svg> <g><text>a <tspan>b</tspan></text></g>\n<g/></svg>";
+    let once = render(&parse::xml(src).unwrap());
+    assert_eq!(
+        once,
+        "<svg>\n\t<g>\n\t\t<text>a <tspan>b</tspan></text>\n\t</g>\n\t<g/>\n</svg>\n"
+    );
+    assert_eq!(render(&parse::xml(&once).unwrap()), once);
+}
+
+#[test]
+fn whitespace_is_kept_unless_trimmed() {
+    let src = "<p> <b>a</b> <i>b</i> </p>";
+    let doc = parse::xml(src).unwrap();
+    let p = doc.children(doc.root())[0];
+    assert_eq!(doc.children(p).len(), 5);
+    assert_eq!(render(&doc), "<p>\n\t<b>a</b>\n\t<i>b</i>\n</p>\n");
+    let doc = parse::xml("<p>x <b>a</b> <i>b</i> </p>").unwrap();
+    assert_eq!(render(&doc), "<p>x <b>a</b> <i>b</i> </p>\n");
+
+    let doc = ParseOptions::xml().trim_text(true).parse(src).unwrap();
+    let p = doc.children(doc.root())[0];
+    assert_eq!(doc.children(p).len(), 2);
+    assert_eq!(render(&doc), "<p>\n\t<b>a</b>\n\t<i>b</i>\n</p>\n");
+}
+
+#[test]
+fn script_content_is_trimmed_only_when_asked() {
+    let src = "<script>\n  go();\n</script>";
+    let doc = parse::html(src).unwrap();
+    assert_eq!(render(&doc), "<script>\n  go();\n</script>\n");
+
+    let doc = ParseOptions::html().trim_text(true).parse(src).unwrap();
+    assert_eq!(render(&doc), "<script>go();</script>\n");
+}
+
+fn render_html(doc: &Document) -> String {
+    let mut s = String::new();
+    RenderOptions::new()
+        .mode(Mode::Html)
+        .build()
+        .render(doc, &mut s)
+        .unwrap();
+    s
+}
+
+#[test]
+fn html_entities_round_trip() {
+    let doc = parse::html("<p title='&rarr;'>&rarr;&eacute;&NotEqualTilde;&amp;rarr;</p>").unwrap();
+    assert_eq!(doc.text(doc.root()), "→é\u{2242}\u{338}&rarr;");
+    let once = render_html(&doc);
+    assert_eq!(once, "<p title=\"→\">→é\u{2242}\u{338}&amp;rarr;</p>\n");
+    assert_eq!(render_html(&parse::html(&once).unwrap()), once);
+
+    // Old names work without the `;`, but not where they run into more of a URL query.
+    let doc = parse::html("<a href='?a=1&copy=2&not'>&copy 2024 &notit; &unknown;</a>").unwrap();
+    let a = doc.children(doc.root())[0];
+    assert_eq!(doc.attr(a, "href"), Some("?a=1&copy=2¬"));
+    assert_eq!(doc.text(a), "© 2024 ¬it; &unknown;");
+}
+
+#[test]
+fn script_ends_only_at_its_end_tag() {
+    let src = "<script>if (a < b && c) x = '</scripts>';</script ><p>b</p>";
+    let doc = parse::html(src).unwrap();
+    let script = doc.children(doc.root())[0];
+    assert_eq!(doc.text(script), "if (a < b && c) x = '</scripts>';");
+    assert_eq!(doc.select("p").unwrap().len(), 1);
+
+    assert_eq!(
+        render_html(&doc),
+        "<script>if (a < b && c) x = '</scripts>';</script>\n<p>b</p>\n"
+    );
+    // The content is still text to XML.
+    assert_eq!(
+        render(&doc),
+        "<script>if (a &lt; b &amp;&amp; c) x = &apos;&lt;/scripts&gt;&apos;;</script>\n<p>b</p>\n"
+    );
+}
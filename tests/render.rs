@@ -0,0 +1,438 @@
+use tagu::build;
+use tagu::error::ErrorKind;
+use tagu::prelude::*;
+use tagu::render::{Mode, RenderOptions};
+
+#[test]
+fn cdata_is_indented_and_split() {
+    let all = build::elem("a").append(build::elem("b").append(build::cdata("x")));
+    let mut s = String::new();
+    tagu::render(all, &mut s).unwrap();
+    assert_eq!(s, "<a>\n\t<b>\n\t\t<![CDATA[x]]>\n\t</b>\n</a>\n");
+
+    let mut s = String::new();
+    tagu::render(build::cdata("c[d[0]]>0"), &mut s).unwrap();
+    assert_eq!(s, "<![CDATA[c[d[0]]]]><![CDATA[>0]]>\n");
+}
+
+#[test]
+fn comment_double_hyphen_only_fails_in_xml() {
+    let doc = tagu::parse::html("<p><!-- a -- b --></p>").unwrap();
+    let mut s = String::new();
+    tagu::render(&doc, &mut s).unwrap();
+    assert_eq!(s, "<p>\n\t<!-- a -- b -->\n</p>\n");
+
+    let mut s = String::new();
+    tagu::render(build::comment("x < \"y\" -- z"), &mut s).unwrap();
+    assert_eq!(s, "<!--x < \"y\" -- z-->\n");
+
+    let mut xml = RenderOptions::new().mode(Mode::Xml).build();
+    let err = xml
+        .render(build::comment("a -- b"), &mut String::new())
+        .unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::Validation(_)));
+    assert_eq!(
+        err.to_string(),
+        "comment \"a -- b\" would end early or is not allowed in this mode"
+    );
+    let mut xhtml = RenderOptions::new().mode(Mode::Xhtml).build();
+    assert!(xhtml
+        .render(build::comment("a-"), &mut String::new())
+        .is_err());
+    let mut html = RenderOptions::new().mode(Mode::Html).build();
+    assert!(html
+        .render(build::comment("a -- b"), &mut String::new())
+        .is_ok());
+    assert!(html
+        .render(build::comment("a --> b"), &mut String::new())
+        .is_err());
+    assert!(html
+        .render(build::comment("a <!-- b"), &mut String::new())
+        .is_err());
+}
+
+#[test]
+fn failed_tag_is_not_left_in_path() {
+    let all = build::from_stack(|w| {
+        let mut w = w.push(build::elem("svg"))?.push(build::elem("g"))?;
+        let err = w.put(build::single("bad name")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "\"bad name\" is not a valid element name (at svg > g > bad name)"
+        );
+        let err = w
+            .put(build::single("rect").with(("bad attr", 1)))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "\"bad attr\" is not a valid attribute name (at svg > g > rect)"
+        );
+        let err = w.put(build::elem("x").append(build::single("y").with(("bad attr", 1))));
+        assert!(err
+            .unwrap_err()
+            .to_string()
+            .ends_with("(at svg > g > x > y)"));
+        let err = w.put(build::single("bad name")).unwrap_err();
+        assert!(err.to_string().ends_with("(at svg > g > bad name)"));
+        w.pop()?.pop()
+    });
+    let mut s = String::new();
+    tagu::render(all, &mut s).unwrap();
+}
+
+#[test]
+fn trailing_newline_is_normalised_to_one() {
+    let render = |val: bool, elem| {
+        let mut s = String::new();
+        RenderOptions::new()
+            .trailing_newline(val)
+            .build()
+            .render(elem, &mut s)
+            .unwrap();
+        s
+    };
+    assert_eq!(render(true, build::raw("x\n\n\n")), "x\n");
+    assert_eq!(render(false, build::raw("x\n\n\n")), "x");
+    assert_eq!(render(true, build::raw("x")), "x\n");
+
+    let mut s = String::new();
+    RenderOptions::new()
+        .trailing_newline(false)
+        .build()
+        .render(
+            build::elem("a").append(build::raw("x\n\n")).inline(),
+            &mut s,
+        )
+        .unwrap();
+    assert_eq!(s, "<a>x\n\n</a>");
+
+    let mut s = String::new();
+    RenderOptions::new()
+        .newline("\r\n")
+        .trailing_newline(true)
+        .build()
+        .render(build::elem("a").append(build::single("b")), &mut s)
+        .unwrap();
+    assert_eq!(s, "<a>\r\n\t<b/>\r\n</a>\r\n");
+}
+
+#[test]
+fn pretty_fmt_indent() {
+    use tagu::render::{PrettyFmt, Renderer};
+
+    let mut fmt = PrettyFmt::new();
+    assert_eq!(fmt.indent(), "\t");
+    fmt.set_indent(" ".repeat(3));
+    fmt.set_indent("  ");
+    assert_eq!(fmt.indent(), "  ");
+    let mut s = String::new();
+    Renderer::new()
+        .with_fmt(fmt)
+        .render(build::elem("a").append(build::single("b")), &mut s)
+        .unwrap();
+    assert_eq!(s, "<a>\n  <b/>\n</a>\n");
+
+    // An indentation built at runtime is restored after `with_tab`.
+    let mut s = String::new();
+    RenderOptions::new()
+        .indent_width(2)
+        .build()
+        .render(
+            build::elem("a")
+                .append(build::elem("b").append(build::single("c")).with_tab("\t"))
+                .append(build::single("d")),
+            &mut s,
+        )
+        .unwrap();
+    assert_eq!(s, "<a>\n\t<b>\n\t\t<c/>\n\t</b>\n  <d/>\n</a>\n");
+}
+
+#[test]
+fn names_are_validated_unless_turned_off() {
+    let err = tagu::render(
+        build::elem("a").append(build::single("b c")),
+        &mut String::new(),
+    )
+    .unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::Validation(_)));
+    assert_eq!(
+        err.to_string(),
+        "\"b c\" is not a valid element name (at a > b c)"
+    );
+    let err = tagu::render(build::single("a").with(("b=", 1)), &mut String::new()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "\"b=\" is not a valid attribute name (at a)"
+    );
+
+    let mut s = String::new();
+    RenderOptions::new()
+        .validate_names(false)
+        .build()
+        .render(build::single("my tag"), &mut s)
+        .unwrap();
+    assert_eq!(s, "<my tag/>\n");
+}
+
+#[test]
+fn declarations() {
+    let all = build::xml_declaration()
+        .encoding("UTF-8")
+        .chain(build::processing_instruction(
+            "xml-stylesheet",
+            "type=\"text/css\" href=\"style.css\"",
+        ))
+        .chain(build::doctype("svg"))
+        .chain(build::single("svg"));
+    let mut s = String::new();
+    tagu::render(all, &mut s).unwrap();
+    assert_eq!(
+        s,
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <?xml-stylesheet type=\"text/css\" href=\"style.css\"?>\n\
+         <!DOCTYPE svg>\n\
+         <svg/>\n"
+    );
+
+    let mut s = String::new();
+    assert!(tagu::render(build::doctype("html><x"), &mut s).is_err());
+    assert!(tagu::render(build::processing_instruction("xml", "a"), &mut s).is_err());
+    let err = tagu::render(build::processing_instruction("php", "?>"), &mut s).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid processing instruction data \"?>\""
+    );
+}
+
+#[test]
+fn declaration_prefixes_are_not_names() {
+    let render = |elem| tagu::render(elem, &mut String::new());
+    assert!(render(build::single("!<script>")).is_err());
+    assert!(render(build::single("?xml")).is_err());
+    assert!(tagu::render(build::elem("!x"), &mut String::new()).is_err());
+
+    // Only a `!` or `?` start makes a declaration, whose tag is not a name.
+    let doctype = build::single("DOCTYPE svg:x")
+        .with_start("!")
+        .with_ending("");
+    let mut s = String::new();
+    RenderOptions::new()
+        .mode(Mode::Xml)
+        .build()
+        .render(doctype, &mut s)
+        .unwrap();
+    assert_eq!(s, "<!DOCTYPE svg:x>\n");
+
+    let err = render(build::single("x").with_start("my&")).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "\"my&x\" is not a valid element name (at my&x)"
+    );
+    let mut s = String::new();
+    tagu::render(build::single("rect").with_start("svg:"), &mut s).unwrap();
+    assert_eq!(s, "<svg:rect/>\n");
+}
+
+#[test]
+fn end_tag_names_are_validated() {
+    // The end tag is the only place the bad name shows up.
+    let mut s = String::new();
+    let err = tagu::render::Renderer::new()
+        .render_sink(
+            build::elem("a").append(build::raw("x")),
+            &mut BadEndTag(tagu::sink::TextSink::new(&mut s)),
+        )
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "\"a b\" is not a valid element name (at a)"
+    );
+}
+
+struct BadEndTag<S>(S);
+
+impl<S: tagu::sink::Sink> tagu::sink::Sink for BadEndTag<S> {
+    fn start_tag(&mut self, name: &str) -> Result<(), tagu::Error> {
+        self.0.start_tag(name)
+    }
+    fn attribute(&mut self, name: &str, value: &str) -> Result<(), tagu::Error> {
+        self.0.attribute(name, value)
+    }
+    fn start_tag_end(&mut self) -> Result<(), tagu::Error> {
+        self.0.start_tag_end()
+    }
+    fn empty_tag_end(&mut self, ending: &str) -> Result<(), tagu::Error> {
+        self.0.empty_tag_end(ending)
+    }
+    fn end_tag(&mut self, _: &str) -> Result<(), tagu::Error> {
+        self.0.end_tag("a b")
+    }
+    fn text(&mut self, text: &str) -> Result<(), tagu::Error> {
+        self.0.text(text)
+    }
+    fn raw(&mut self, markup: &str) -> Result<(), tagu::Error> {
+        self.0.raw(markup)
+    }
+    fn comment(&mut self, text: &str) -> Result<(), tagu::Error> {
+        self.0.comment(text)
+    }
+}
+
+#[test]
+fn duplicate_attrs_default_is_compatible() {
+    let mut s = String::new();
+    let input = build::single("input")
+        .with(build::flag("disabled"))
+        .with(build::flag("disabled"));
+    tagu::render(input, &mut s).unwrap();
+    assert_eq!(s, "<input disabled=\"disabled\" disabled=\"disabled\"/>\n");
+}
+
+#[test]
+fn duplicate_attrs_error_leaves_no_partial_tag() {
+    use tagu::render::DuplicateAttrs;
+
+    let mut s = String::new();
+    let err = RenderOptions::new()
+        .duplicate_attrs(DuplicateAttrs::Error)
+        .build()
+        .render(
+            build::elem("p").append(
+                build::single("input")
+                    .with(build::flag("disabled"))
+                    .with(build::flag("disabled")),
+            ),
+            &mut s,
+        )
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "duplicate attribute \"disabled\" (at p > input)"
+    );
+    assert_eq!(s, "<p>\n\t");
+}
+
+#[test]
+fn duplicate_attrs_policies() {
+    use tagu::render::DuplicateAttrs;
+
+    let render = |policy| {
+        let mut s = String::new();
+        RenderOptions::new()
+            .duplicate_attrs(policy)
+            .trailing_newline(false)
+            .build()
+            .render(
+                build::single("button")
+                    .with(("class", "btn"))
+                    .with(("style", "color: red;"))
+                    .with(("id", "a"))
+                    .with(("class", "btn primary"))
+                    .with(("style", "margin: 0"))
+                    .with(("id", "b")),
+                &mut s,
+            )
+            .map(|_| s)
+    };
+    assert_eq!(
+        render(DuplicateAttrs::Error).unwrap_err().to_string(),
+        "duplicate attribute \"class\" (at button)"
+    );
+    assert_eq!(
+        render(DuplicateAttrs::LastWins).unwrap(),
+        "<button class=\"btn primary\" style=\"margin: 0\" id=\"b\"/>"
+    );
+    assert_eq!(
+        render(DuplicateAttrs::Merge).unwrap(),
+        "<button class=\"btn primary\" style=\"color: red; margin: 0\" id=\"b\"/>"
+    );
+}
+
+#[test]
+fn duplicate_attrs_ignore_case_in_html() {
+    use tagu::render::DuplicateAttrs;
+
+    let render = |mode, policy| {
+        let mut s = String::new();
+        RenderOptions::new()
+            .mode(mode)
+            .duplicate_attrs(policy)
+            .trailing_newline(false)
+            .build()
+            .render(
+                build::single("div")
+                    .with(("CLASS", "a"))
+                    .with(("class", "b"))
+                    .with(("Id", "x"))
+                    .with(("id", "y")),
+                &mut s,
+            )
+            .map(|_| s)
+    };
+    assert!(render(Mode::Html, DuplicateAttrs::Error).is_err());
+    assert_eq!(
+        render(Mode::Html, DuplicateAttrs::Merge).unwrap(),
+        "<div CLASS=\"a b\" Id=\"y\"></div>"
+    );
+    assert_eq!(
+        render(Mode::Xml, DuplicateAttrs::Error).unwrap(),
+        "<div CLASS=\"a\" class=\"b\" Id=\"x\" id=\"y\"/>"
+    );
+}
+
+#[test]
+fn html_void_elements_have_no_end_tag() {
+    let mut html = RenderOptions::new().mode(Mode::Html).build();
+    assert!(html
+        .render(build::comment("a -- b"), &mut String::new())
+        .is_ok());
+    let mut s = String::new();
+    html.render(build::elem("p").append(build::elem("br")), &mut s)
+        .unwrap();
+    assert_eq!(s, "<p>\n\t<br>\n</p>\n");
+
+    let err = html
+        .render(
+            build::elem("br").append(build::raw("x")),
+            &mut String::new(),
+        )
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "void element br cannot have children (at br)"
+    );
+}
+
+#[test]
+fn modes_write_empty_elements_and_flags() {
+    let page = || {
+        build::elem("p")
+            .append(build::single("br"))
+            .append(build::single("span"))
+            .append(build::elem("b"))
+            .append(build::single("input").with(build::flag("checked")))
+            .inline()
+    };
+    let render = |mode| {
+        let mut s = String::new();
+        RenderOptions::new()
+            .mode(mode)
+            .trailing_newline(false)
+            .build()
+            .render(page(), &mut s)
+            .unwrap();
+        s
+    };
+    assert_eq!(
+        render(Mode::Xml),
+        "<p><br/><span/><b/><input checked=\"checked\"/></p>"
+    );
+    assert_eq!(
+        render(Mode::Xhtml),
+        "<p><br /><span></span><b></b><input checked=\"checked\" /></p>"
+    );
+    assert_eq!(
+        render(Mode::Html),
+        "<p><br><span></span><b></b><input checked></p>"
+    );
+}
@@ -0,0 +1,77 @@
+use tagu::build;
+use tagu::prelude::*;
+use tagu::render::RenderOptions;
+
+// A writer that is pending every other poll and records each write.
+#[derive(Default)]
+struct Pending {
+    out: Vec<u8>,
+    writes: Vec<usize>,
+    ready: bool,
+}
+
+impl tagu::render::AsyncWrite for Pending {
+    fn poll_write(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context,
+        buf: &[u8],
+    ) -> std::task::Poll<std::io::Result<usize>> {
+        self.ready = !self.ready;
+        if !self.ready {
+            cx.waker().wake_by_ref();
+            return std::task::Poll::Pending;
+        }
+        self.out.extend_from_slice(buf);
+        self.writes.push(buf.len());
+        std::task::Poll::Ready(Ok(buf.len()))
+    }
+    fn poll_flush(
+        self: std::pin::Pin<&mut Self>,
+        _: &mut std::task::Context,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+}
+
+fn block_on<F: std::future::Future>(fut: F) -> F::Output {
+    let mut fut = std::pin::pin!(fut);
+    let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+    loop {
+        if let std::task::Poll::Ready(res) = fut.as_mut().poll(&mut cx) {
+            return res;
+        }
+    }
+}
+
+#[test]
+fn render_async_hands_on_every_chunk_size() {
+    let big = || {
+        build::elem("g").append(build::from_iter(
+            (0..20).map(|i| build::single("rect").with(("x", i))),
+        ))
+    };
+    let mut whole = String::new();
+    tagu::render(build::elem("svg").append(big()), &mut whole).unwrap();
+
+    let mut writer = Pending::default();
+    let mut renderer = RenderOptions::new().chunk_size(32).build();
+    block_on(renderer.render_async(build::elem("svg"), [big()], &mut writer)).unwrap();
+    assert_eq!(String::from_utf8(writer.out).unwrap(), whole);
+    assert!(writer.writes.len() > whole.len() / 32);
+    assert!(writer.writes.iter().all(|&n| n <= 32));
+}
+
+#[test]
+fn render_async_fails_with_the_render_error() {
+    let mut writer = Pending::default();
+    let res = block_on(tagu::render_async(
+        build::elem("svg"),
+        [build::single("bad name")],
+        &mut writer,
+    ));
+    let err = res.unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "\"bad name\" is not a valid element name (at svg > bad name)"
+    );
+}
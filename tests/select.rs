@@ -0,0 +1,63 @@
+use tagu::build;
+use tagu::dom::Document;
+use tagu::prelude::*;
+use tagu::select::Selector;
+
+#[test]
+fn select_then_patch() {
+    let series = |name: &'static str| {
+        build::elem("g")
+            .with(("class", "series"))
+            .append(build::single("path").with(("id", name)))
+    };
+    let all = build::elem("svg")
+        .append(series("a"))
+        .append(series("b"))
+        .append(build::single("path").with(("id", "axis")));
+
+    let mut doc = Document::capture(all).unwrap();
+    let paths = doc.select("g.series > path").unwrap();
+    assert_eq!(paths.len(), 2);
+    for p in paths {
+        doc.set_attr(p, "stroke", "red");
+    }
+    assert_eq!(doc.select("[stroke=red]#b").unwrap().len(), 1);
+    assert_eq!(doc.select("svg path").unwrap().len(), 3);
+    assert_eq!(doc.select("#axis, [id^=a]").unwrap().len(), 2);
+}
+
+#[test]
+fn namespaced_and_escaped_names() {
+    let doc = tagu::parse::xml(
+        "<svg xmlns:xlink='urn:x'>\
+         <use xlink:href='#a'/><use href='#b'/><svg:rect class='a:b'/>\
+         </svg>",
+    )
+    .unwrap();
+    let ids = |s: &str| doc.select(s).unwrap().len();
+    assert_eq!(ids("use[xlink:href]"), 1);
+    assert_eq!(ids("use[xlink\\:href='#a']"), 1);
+    assert_eq!(ids("[href]"), 1);
+    assert_eq!(ids("svg\\:rect"), 1);
+    assert_eq!(ids("svg\\3A rect"), 1);
+    assert_eq!(ids(".a\\:b"), 1);
+    assert_eq!(ids("svg > svg\\:rect.a\\3a b"), 1);
+}
+
+#[test]
+fn selector_errors() {
+    for bad in [
+        "", "g >", "a b,", "[x", "[x=]", "[x~y]", "a\\", ".", "svg:rect", "a..b",
+    ] {
+        let err = Selector::parse(bad).unwrap_err();
+        assert_eq!(err.selector(), bad);
+        assert!(err.position() <= bad.len(), "{:?}", bad);
+    }
+    assert_eq!(
+        Selector::parse("a [b").unwrap_err().to_string(),
+        "invalid selector \"a [b\" at position 4"
+    );
+    let doc = tagu::parse::xml("<a/>").unwrap();
+    assert_eq!(doc.select("a >").unwrap_err().position(), 3);
+    assert!("a >".parse::<Selector>().is_err());
+}
@@ -0,0 +1,120 @@
+use tagu::build;
+use tagu::prelude::*;
+use tagu::sink::Sink;
+
+///
+/// Records the events it receives.
+///
+#[derive(Default)]
+struct Events(Vec<String>);
+
+impl Sink for Events {
+    fn start_tag(&mut self, name: &str) -> Result<(), tagu::Error> {
+        self.0.push(format!("start {}", name));
+        Ok(())
+    }
+    fn attribute(&mut self, name: &str, value: &str) -> Result<(), tagu::Error> {
+        self.0.push(format!("attr {}={}", name, value));
+        Ok(())
+    }
+    fn start_tag_end(&mut self) -> Result<(), tagu::Error> {
+        Ok(())
+    }
+    fn empty_tag_end(&mut self, _: &str) -> Result<(), tagu::Error> {
+        self.0.push("empty".to_string());
+        Ok(())
+    }
+    fn end_tag(&mut self, name: &str) -> Result<(), tagu::Error> {
+        self.0.push(format!("end {}", name));
+        Ok(())
+    }
+    fn text(&mut self, text: &str) -> Result<(), tagu::Error> {
+        self.0.push(format!("text {}", text));
+        Ok(())
+    }
+    fn raw(&mut self, markup: &str) -> Result<(), tagu::Error> {
+        self.0.push(format!("raw {}", markup));
+        Ok(())
+    }
+    fn comment(&mut self, text: &str) -> Result<(), tagu::Error> {
+        self.0.push(format!("comment {}", text));
+        Ok(())
+    }
+}
+
+#[test]
+fn with_start_is_not_a_start_tag() {
+    let all = build::single("DOCTYPE html")
+        .with_start("!")
+        .with_ending("")
+        .chain(build::single("br").with(("class", "a")));
+    let mut sink = Events::default();
+    tagu::render_sink(all, &mut sink).unwrap();
+    assert_eq!(
+        sink.0,
+        ["raw <!DOCTYPE html>", "start br", "attr class=a", "empty",]
+    );
+
+    let mut s = String::new();
+    let all = build::single("xml-stylesheet")
+        .with_start("?")
+        .with(("href", "a\"b.css"))
+        .with_ending("?");
+    tagu::render(all, &mut s).unwrap();
+    assert_eq!(s, "<?xml-stylesheet href=\"a&quot;b.css\"?>\n");
+
+    let bad = build::single("DOCTYPE html><script").with_start("!");
+    assert!(tagu::render(bad, &mut String::new()).is_err());
+    let bad = build::single("DOCTYPE").with_start("!").with(("a>b", "c"));
+    assert!(tagu::render(bad, &mut String::new()).is_err());
+}
+
+#[test]
+#[allow(deprecated)]
+fn attribute_markup_becomes_attributes() {
+    use std::fmt::Write;
+    use tagu::attr::AttrClosure;
+
+    let input = build::single("input")
+        .with(AttrClosure::new(|w| {
+            write!(w.writer(), " disabled size={}", 10)
+        }))
+        .with(("id", "a"));
+    let mut events = Events::default();
+    tagu::render_sink(input, &mut events).unwrap();
+    assert_eq!(
+        events.0,
+        [
+            "start input",
+            "attr disabled=disabled",
+            "attr size=10",
+            "attr id=a",
+            "empty"
+        ]
+    );
+
+    let bad = build::single("input").with(AttrClosure::new(|w| w.writer().write_str(" a<b")));
+    let err = tagu::render(bad, &mut String::new()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "\"a<b\" is not a valid attribute name (at input)"
+    );
+}
+
+#[test]
+fn write_attr_formats_the_value() {
+    use tagu::attr::AttrClosure;
+
+    let points = [(0, 0), (10, 5)];
+    let k = build::single("polyline").with(AttrClosure::new(|w| {
+        w.write_attr("points", |v| {
+            for (x, y) in points {
+                write!(v, "{},{} ", x, y)?;
+            }
+            v.write_str("<")
+        })
+    }));
+    let mut s = String::new();
+    tagu::render(k, &mut s).unwrap();
+    assert_eq!(s, "<polyline points=\"0,0 10,5 &lt;\"/>\n");
+}
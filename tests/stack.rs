@@ -0,0 +1,125 @@
+use tagu::build;
+use tagu::prelude::*;
+
+#[derive(Debug)]
+enum ReportError {
+    Render(tagu::Error),
+    Missing(&'static str),
+}
+
+impl From<tagu::Error> for ReportError {
+    fn from(e: tagu::Error) -> Self {
+        ReportError::Render(e)
+    }
+}
+
+impl std::fmt::Display for ReportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ReportError::Render(e) => write!(f, "{}", e),
+            ReportError::Missing(name) => write!(f, "missing {}", name),
+        }
+    }
+}
+
+impl std::error::Error for ReportError {}
+
+#[test]
+fn session_returns_its_own_error() {
+    let mut s = String::new();
+    build::try_from_stack(|w| {
+        let w = w.push(build::elem("svg"))?;
+        Ok::<_, ReportError>(w.pop()?)
+    })
+    .render(&mut s)
+    .unwrap();
+    assert_eq!(s, "<svg>\n</svg>\n");
+
+    let res = build::try_from_stack(|w| {
+        let _ = w.push(build::elem("svg"))?;
+        Err(ReportError::Missing("radius"))
+    })
+    .render(&mut String::new());
+    assert!(matches!(res, Err(ReportError::Missing("radius"))));
+
+    // Errors from tagu keep their kind and path.
+    let res = build::try_from_stack(|w| {
+        let w = w.push(build::elem("svg"))?.push(build::elem("bad name"))?;
+        Ok::<_, ReportError>(w.pop()?.pop()?)
+    })
+    .render(&mut String::new());
+    match res {
+        Err(ReportError::Render(e)) => assert_eq!(e.path(), ["svg", "bad name"]),
+        res => panic!("{:?}", res),
+    }
+
+    // Inside other elements, the error is boxed into a tagu::Error.
+    let all = build::elem("a").append(build::try_from_stack(|w| {
+        let _ = w.push(build::elem("svg"))?;
+        Err(ReportError::Missing("radius"))
+    }));
+    let err = tagu::render(all, &mut String::new()).unwrap_err();
+    assert!(matches!(err.kind(), tagu::error::ErrorKind::User(_)));
+    assert_eq!(err.to_string(), "missing radius (at a > svg)");
+}
+
+// Fails every write once `len` bytes are written.
+struct FailAfter(String, usize);
+
+impl std::fmt::Write for FailAfter {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        if self.0.len() + s.len() > self.1 {
+            return Err(std::fmt::Error);
+        }
+        self.0.push_str(s);
+        Ok(())
+    }
+}
+
+#[test]
+fn failed_pop_reports_the_element_it_closes() {
+    let session = || {
+        build::from_stack(|w| {
+            let w = w.push(build::elem("svg"))?.push(build::elem("g"))?;
+            w.pop()?.pop()
+        })
+    };
+    let mut s = String::new();
+    tagu::render(session(), &mut s).unwrap();
+
+    let len = s.find("</g>").unwrap();
+    let err = tagu::render(session(), FailAfter(String::new(), len)).unwrap_err();
+    assert!(matches!(err.kind(), tagu::error::ErrorKind::Fmt));
+    assert_eq!(err.path(), ["svg", "g"]);
+
+    let res = build::try_from_stack(|w| {
+        let w = w.push(build::elem("svg"))?;
+        Ok::<_, ReportError>(w.pop()?)
+    })
+    .render(FailAfter(String::new(), 0));
+    match res {
+        Err(ReportError::Render(e)) => assert!(matches!(e.kind(), tagu::error::ErrorKind::Fmt)),
+        res => panic!("{:?}", res),
+    }
+}
+
+#[test]
+fn elem_outer_renders_without_a_closure() {
+    use tagu::stack::{ElemOuter, ElemStack, Sentinel};
+
+    struct Circle(&'static str);
+    impl ElemOuter for Circle {
+        fn render<'a>(
+            self,
+            w: ElemStack<'a, Sentinel>,
+        ) -> Result<ElemStack<'a, Sentinel>, tagu::Error> {
+            let mut w = w.push(build::elem("g"))?;
+            w.put(build::single("circle").with(("r", self.0)))?;
+            w.pop()
+        }
+    }
+
+    let mut s = String::new();
+    tagu::render(Circle("5"), &mut s).unwrap();
+    assert_eq!(s, "<g>\n\t<circle r=\"5\"/>\n</g>\n");
+}